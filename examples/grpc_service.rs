@@ -15,15 +15,16 @@
 //
 // Then:
 //
-//     cargo run --example grpc_service
+//     cargo run --example grpc_service --features signal
 
-use ez_rust_discovery::{ServiceConfig, ServiceManager};
+use ez_rust_discovery::{ServiceConfig, ServiceManager, shutdown_signal};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing_subscriber::fmt()
         .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()),
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "info,ez_rust_discovery=debug".into()),
         )
         .init();
 
@@ -36,8 +37,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // 3. TODO: start your gRPC / HTTP server here.
 
-    // 4. Block until a termination signal arrives.
-    tokio::signal::ctrl_c().await?;
+    // 4. Wait for Ctrl+C or SIGTERM (what orchestrators send on shutdown).
+    shutdown_signal().await?;
 
     // 5. Gracefully deregister.
     manager.deregister().await?;
@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use nacos_sdk::api::naming::ServiceInstance;
+
+/// A client-side load-balancing policy, used with
+/// [`ServiceManager::select_with`](crate::ServiceManager::select_with).
+///
+/// Implement it to plug in custom policies such as least-connections; the crate ships
+/// [`RoundRobinBalancer`], [`RandomBalancer`] and [`WeightedBalancer`].
+pub trait LoadBalancer: Send + Sync {
+    /// Choose one of `instances`, or `None` if none is acceptable. The slice holds only
+    /// healthy, enabled instances and may be empty.
+    fn choose<'a>(&self, instances: &'a [ServiceInstance]) -> Option<&'a ServiceInstance>;
+}
+
+/// Rotates through the instances in order. Keep one per service so each gets its own cursor.
+#[derive(Debug, Default)]
+pub struct RoundRobinBalancer {
+    next: AtomicUsize,
+}
+
+impl LoadBalancer for RoundRobinBalancer {
+    fn choose<'a>(&self, instances: &'a [ServiceInstance]) -> Option<&'a ServiceInstance> {
+        if instances.is_empty() {
+            return None;
+        }
+        let n = self.next.fetch_add(1, Ordering::Relaxed);
+        instances.get(n % instances.len())
+    }
+}
+
+/// Picks uniformly at random, ignoring weights.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RandomBalancer;
+
+impl LoadBalancer for RandomBalancer {
+    fn choose<'a>(&self, instances: &'a [ServiceInstance]) -> Option<&'a ServiceInstance> {
+        if instances.is_empty() {
+            return None;
+        }
+        instances.get(rand::random_range(0..instances.len()))
+    }
+}
+
+/// Picks at random with probability proportional to weight; weight-`0` instances are never
+/// chosen.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WeightedBalancer;
+
+impl LoadBalancer for WeightedBalancer {
+    fn choose<'a>(&self, instances: &'a [ServiceInstance]) -> Option<&'a ServiceInstance> {
+        pick_weighted(instances, rand::random())
+    }
+}
+
+/// A [`RoundRobinBalancer`] per service.
+#[derive(Debug, Default)]
+pub(crate) struct RoundRobin {
+    balancers: Mutex<HashMap<String, Arc<RoundRobinBalancer>>>,
+}
+
+impl RoundRobin {
+    /// Pick the next instance for `service`, rotating through `instances` across calls.
+    pub(crate) fn pick<'a>(
+        &self,
+        service: &str,
+        instances: &'a [ServiceInstance],
+    ) -> Option<&'a ServiceInstance> {
+        self.balancer(service).choose(instances)
+    }
+
+    fn balancer(&self, service: &str) -> Arc<RoundRobinBalancer> {
+        let mut balancers = self
+            .balancers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        balancers.entry(service.to_string()).or_default().clone()
+    }
+}
+
+/// Per-service state of nginx-style smooth weighted round robin: the running "current
+/// weight" of each instance, keyed by `ip:port`.
+#[derive(Debug, Default)]
+pub(crate) struct SmoothWeighted {
+    current: Mutex<HashMap<String, HashMap<String, f64>>>,
+}
+
+impl SmoothWeighted {
+    /// Pick the next instance for `service`: every positive-weight instance gains its weight,
+    /// the one with the highest running total wins and pays back the sum of all weights. Over
+    /// any window of `sum` picks each instance is chosen in proportion to its weight, with
+    /// picks interleaved rather than bunched.
+    pub(crate) fn pick<'a>(
+        &self,
+        service: &str,
+        instances: &'a [ServiceInstance],
+    ) -> Option<&'a ServiceInstance> {
+        let mut services = self.current.lock().unwrap_or_else(PoisonError::into_inner);
+        let current = services.entry(service.to_string()).or_default();
+        let keys: Vec<String> = instances
+            .iter()
+            .map(|i| format!("{}:{}", i.ip, i.port))
+            .collect();
+        // Forget instances that left, so a returning one starts from scratch.
+        current.retain(|key, _| keys.contains(key));
+
+        let mut total = 0.0;
+        let mut best: Option<(usize, f64)> = None;
+        for (idx, instance) in instances.iter().enumerate() {
+            if instance.weight <= 0.0 || !instance.weight.is_finite() {
+                continue;
+            }
+            let weight = current.entry(keys[idx].clone()).or_default();
+            *weight += instance.weight;
+            total += instance.weight;
+            if best.is_none_or(|(_, top)| *weight > top) {
+                best = Some((idx, *weight));
+            }
+        }
+        let (idx, _) = best?;
+        if let Some(weight) = current.get_mut(&keys[idx]) {
+            *weight -= total;
+        }
+        instances.get(idx)
+    }
+}
+
+/// Pick an instance with probability proportional to its weight, given a uniform `sample`
+/// in `[0, 1)`. Instances with a non-positive weight are never chosen.
+pub(crate) fn pick_weighted(
+    instances: &[ServiceInstance],
+    sample: f64,
+) -> Option<&ServiceInstance> {
+    let total: f64 = instances.iter().map(|i| i.weight.max(0.0)).sum();
+    if total <= 0.0 {
+        return None;
+    }
+    let mut remaining = sample * total;
+    let mut last = None;
+    for instance in instances.iter().filter(|i| i.weight > 0.0) {
+        if remaining < instance.weight {
+            return Some(instance);
+        }
+        remaining -= instance.weight;
+        last = Some(instance);
+    }
+    // Guard against floating-point drift pushing `remaining` past the final bucket.
+    last
+}
+
+/// Virtual nodes placed on the hash ring per instance; more nodes give a smoother spread.
+const VIRTUAL_NODES: u32 = 160;
+
+/// Map `key` to an instance via consistent hashing over `instances`.
+///
+/// Each instance is placed on a ring at [`VIRTUAL_NODES`] points derived from its `ip:port`,
+/// so adding or removing one instance only remaps the keys that land on its points.
+pub(crate) fn pick_by_hash<'a>(
+    instances: &'a [ServiceInstance],
+    key: &str,
+) -> Option<&'a ServiceInstance> {
+    let mut ring: Vec<(u64, &ServiceInstance)> = instances
+        .iter()
+        .flat_map(|instance| {
+            (0..VIRTUAL_NODES).map(move |v| {
+                let node = format!("{}:{}#{v}", instance.ip, instance.port);
+                (fnv1a(node.as_bytes()), instance)
+            })
+        })
+        .collect();
+    ring.sort_unstable_by_key(|(hash, _)| *hash);
+    let hash = fnv1a(key.as_bytes());
+    let idx = ring.partition_point(|(h, _)| *h < hash);
+    ring.get(idx).or_else(|| ring.first()).map(|(_, i)| *i)
+}
+
+/// 64-bit FNV-1a; stable across processes and Rust versions, unlike `DefaultHasher`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    fn weighted(weights: &[f64]) -> Vec<ServiceInstance> {
+        weights
+            .iter()
+            .enumerate()
+            .map(|(port, &weight)| ServiceInstance {
+                ip: "10.0.0.1".to_string(),
+                port: port as i32,
+                weight,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn weighted_respects_weight_buckets() {
+        let list = weighted(&[1.0, 0.0, 3.0]);
+        assert_eq!(pick_weighted(&list, 0.0).unwrap().port, 0);
+        assert_eq!(pick_weighted(&list, 0.24).unwrap().port, 0);
+        assert_eq!(pick_weighted(&list, 0.25).unwrap().port, 2);
+        assert_eq!(pick_weighted(&list, 0.999_999).unwrap().port, 2);
+    }
+
+    #[test]
+    fn weighted_never_picks_zero_weight() {
+        let list = weighted(&[0.0, 2.0, 0.0]);
+        for step in 0..100 {
+            let sample = f64::from(step) / 100.0;
+            assert_eq!(pick_weighted(&list, sample).unwrap().port, 1);
+        }
+        assert!(pick_weighted(&weighted(&[0.0, 0.0]), 0.5).is_none());
+    }
+
+    fn instances(ports: &[i32]) -> Vec<ServiceInstance> {
+        ports
+            .iter()
+            .map(|&port| ServiceInstance {
+                ip: "10.0.0.1".to_string(),
+                port,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn hash_is_deterministic() {
+        let list = instances(&[1, 2, 3, 4]);
+        for key in ["user-1", "user-2", "session-abc"] {
+            let first = pick_by_hash(&list, key).unwrap().port;
+            assert_eq!(pick_by_hash(&list, key).unwrap().port, first);
+        }
+        assert!(pick_by_hash(&[], "user-1").is_none());
+    }
+
+    #[test]
+    fn hash_only_moves_keys_of_removed_instance() {
+        let full = instances(&[1, 2, 3, 4]);
+        let reduced = instances(&[1, 2, 4]);
+        for n in 0..1000 {
+            let key = format!("key-{n}");
+            let before = pick_by_hash(&full, &key).unwrap().port;
+            let after = pick_by_hash(&reduced, &key).unwrap().port;
+            if before != 3 {
+                assert_eq!(before, after, "{key} moved unnecessarily");
+            }
+        }
+    }
+
+    #[test]
+    fn round_robin_rotates_per_service() {
+        let rr = RoundRobin::default();
+        let a = instances(&[1, 2, 3]);
+        let b = instances(&[7, 8]);
+        let picked: Vec<i32> = (0..4).map(|_| rr.pick("a", &a).unwrap().port).collect();
+        assert_eq!(picked, vec![1, 2, 3, 1]);
+        assert_eq!(rr.pick("b", &b).unwrap().port, 7);
+        assert_eq!(rr.pick("a", &a).unwrap().port, 2);
+    }
+
+    #[test]
+    fn smooth_weighted_interleaves_picks() {
+        let sw = SmoothWeighted::default();
+        let list = weighted(&[5.0, 1.0, 1.0]);
+        let picked: Vec<i32> = (0..7).map(|_| sw.pick("a", &list).unwrap().port).collect();
+        assert_eq!(picked, vec![0, 0, 1, 0, 2, 0, 0]);
+        assert!(sw.pick("b", &weighted(&[0.0])).is_none());
+    }
+
+    #[test]
+    fn shipped_balancers_choose_eligible_instances() {
+        let rr = RoundRobinBalancer::default();
+        let list = instances(&[1, 2]);
+        let picked: Vec<i32> = (0..3).map(|_| rr.choose(&list).unwrap().port).collect();
+        assert_eq!(picked, vec![1, 2, 1]);
+
+        let random = RandomBalancer.choose(&list).unwrap().port;
+        assert!(random == 1 || random == 2);
+        let list = weighted(&[0.0, 1.0]);
+        assert_eq!(WeightedBalancer.choose(&list).unwrap().port, 1);
+
+        for lb in [&rr as &dyn LoadBalancer, &RandomBalancer, &WeightedBalancer] {
+            assert!(lb.choose(&[]).is_none());
+        }
+    }
+
+    #[test]
+    fn round_robin_empty_returns_none() {
+        assert!(RoundRobin::default().pick("a", &[]).is_none());
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use nacos_sdk::api::naming::ServiceInstance;
+
+type Key = (String, String);
+
+/// Recently fetched instance lists, keyed by service name and group.
+#[derive(Debug, Default)]
+pub(crate) struct InstanceCache {
+    entries: Mutex<HashMap<Key, Entry>>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    instances: Vec<ServiceInstance>,
+    fetched_at: Instant,
+    refreshing: bool,
+}
+
+impl InstanceCache {
+    /// The cached instances of `service_name`, if any, and whether the caller should refresh
+    /// them: true exactly once per expiry, until [`store`](Self::store) or
+    /// [`refresh_failed`](Self::refresh_failed) is called.
+    pub(crate) fn lookup(
+        &self,
+        service_name: &str,
+        group: &str,
+        ttl: Duration,
+    ) -> Option<(Vec<ServiceInstance>, bool)> {
+        let mut entries = self.entries();
+        let entry = entries.get_mut(&key(service_name, group))?;
+        let refresh = entry.fetched_at.elapsed() >= ttl && !entry.refreshing;
+        if refresh {
+            entry.refreshing = true;
+        }
+        Some((entry.instances.clone(), refresh))
+    }
+
+    pub(crate) fn store(&self, service_name: &str, group: &str, instances: Vec<ServiceInstance>) {
+        self.entries().insert(
+            key(service_name, group),
+            Entry {
+                instances,
+                fetched_at: Instant::now(),
+                refreshing: false,
+            },
+        );
+    }
+
+    /// Allow the next [`lookup`](Self::lookup) to trigger another refresh.
+    pub(crate) fn refresh_failed(&self, service_name: &str, group: &str) {
+        if let Some(entry) = self.entries().get_mut(&key(service_name, group)) {
+            entry.refreshing = false;
+        }
+    }
+
+    pub(crate) fn invalidate(&self, service_name: &str, group: &str) {
+        self.entries().remove(&key(service_name, group));
+    }
+
+    fn entries(&self) -> MutexGuard<'_, HashMap<Key, Entry>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn key(service_name: &str, group: &str) -> Key {
+    (service_name.to_string(), group.to_string())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expired_entry_requests_one_refresh() {
+        let cache = InstanceCache::default();
+        assert!(cache.lookup("svc", "G", Duration::ZERO).is_none());
+
+        cache.store("svc", "G", vec![ServiceInstance::default()]);
+        let (instances, refresh) = cache.lookup("svc", "G", Duration::from_secs(60)).unwrap();
+        assert_eq!(instances.len(), 1);
+        assert!(!refresh);
+
+        assert!(cache.lookup("svc", "G", Duration::ZERO).unwrap().1);
+        assert!(!cache.lookup("svc", "G", Duration::ZERO).unwrap().1);
+        cache.refresh_failed("svc", "G");
+        assert!(cache.lookup("svc", "G", Duration::ZERO).unwrap().1);
+
+        cache.invalidate("svc", "G");
+        assert!(cache.lookup("svc", "G", Duration::ZERO).is_none());
+    }
+}
@@ -1,48 +1,147 @@
 use std::collections::HashMap;
 use std::env;
+use std::net::SocketAddr;
+use std::sync::{PoisonError, RwLock};
+use std::time::Duration;
+
+use tracing::warn;
 
 use crate::error::{Error, Result};
+use crate::host::{HostResolver, HostStrategy};
+use crate::port::Port;
 
 /// Default service group, equivalent to nacos's `DEFAULT_GROUP`.
 pub const DEFAULT_GROUP: &str = "DEFAULT_GROUP";
 
+/// Process-wide override of [`DEFAULT_GROUP`], installed by [`set_default_group`].
+static GROUP_OVERRIDE: RwLock<Option<String>> = RwLock::new(None);
+
+/// Change the group used by every [`ServiceConfigBuilder`] that does not set one explicitly,
+/// for teams that keep all services in a single non-default group.
+///
+/// Call it once at startup, before building configurations; builders that already ran keep
+/// their group. [`clear_default_group`] restores [`DEFAULT_GROUP`].
+pub fn set_default_group(group: impl Into<String>) {
+    *GROUP_OVERRIDE
+        .write()
+        .unwrap_or_else(PoisonError::into_inner) = Some(group.into());
+}
+
+/// Undo [`set_default_group`], falling back to [`DEFAULT_GROUP`] again.
+pub fn clear_default_group() {
+    *GROUP_OVERRIDE
+        .write()
+        .unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+/// The group applied when none is configured: the [`set_default_group`] override if any,
+/// otherwise [`DEFAULT_GROUP`].
+pub fn default_group() -> String {
+    GROUP_OVERRIDE
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+        .unwrap_or_else(|| DEFAULT_GROUP.to_string())
+}
+
 /// Default instance weight.
 pub const DEFAULT_WEIGHT: f64 = 1.0;
 
 /// Metadata key for the gRPC port (used by clients to distinguish multi-protocol endpoints).
 pub const META_GRPC_PORT: &str = "gRPC_port";
 
+/// Metadata key for the HTTP port, set when an HTTP port is configured alongside gRPC.
+pub const META_HTTP_PORT: &str = "http_port";
+
+/// Metadata key for the zone, set together with the cluster name by
+/// [`ServiceConfigBuilder::zone`].
+pub const META_ZONE: &str = "zone";
+
+/// Metadata key for the transport clients should dial the instance with, set by
+/// [`ServiceConfigBuilder::protocol`].
+pub const META_PROTOCOL: &str = "protocol";
+
+/// Accepted values of the [`META_PROTOCOL`] metadata entry.
+const PROTOCOLS: [&str; 4] = ["grpc", "grpcs", "http", "https"];
+
 /// Names of the environment variables consumed by [`ServiceConfig::from_env`].
 pub mod env_keys {
-    /// Nacos server address (`host:port`).
+    /// Nacos server address (`host:port`), or a comma-separated list for a cluster.
     pub const NACOS_ADDR: &str = "NACOS_ADDR";
-    /// Nacos namespace id.
+    /// Address server that hands out the Nacos server list; makes `NACOS_ADDR` optional.
+    pub const NACOS_ENDPOINT: &str = "NACOS_ENDPOINT";
+    /// Nacos namespace id (defaults to the public namespace).
     pub const NACOS_NAMESPACE: &str = "NACOS_NAMESPACE";
     /// Nacos auth username.
     pub const NACOS_USERNAME: &str = "NACOS_USERNAME";
     /// Nacos auth password.
     pub const NACOS_PASSWORD: &str = "NACOS_PASSWORD";
-    /// Local listening address (`host:port`); only the port is used for registration.
+    /// Nacos access key for AK/SK auth (requires the `aliyun-auth` feature).
+    pub const NACOS_AK: &str = "NACOS_AK";
+    /// Nacos secret key for AK/SK auth (requires the `aliyun-auth` feature).
+    pub const NACOS_SK: &str = "NACOS_SK";
+    /// Local listening address (`host:port` or a bare port); only the port is used for
+    /// registration.
     pub const SERVICE_ADDR: &str = "SERVICE_ADDR";
+    /// Advertised port; alternative to [`SERVICE_ADDR`] and takes precedence over it.
+    pub const SERVICE_PORT: &str = "SERVICE_PORT";
     /// Service name.
     pub const SERVICE_NAME: &str = "SERVICE_NAME";
     /// Advertised host registered to Nacos (defaults to the local IP).
     pub const SERVICE_HOST: &str = "SERVICE_HOST";
+    /// Network interface whose address is advertised when `SERVICE_HOST` is unset, e.g.
+    /// `eth0` on multi-homed hosts.
+    pub const SERVICE_IFACE: &str = "SERVICE_IFACE";
+    /// Whether to advertise the local IPv6 address instead of IPv4 when `SERVICE_HOST` and
+    /// `SERVICE_IFACE` are unset, `true` or `false` (defaults to `false`).
+    pub const SERVICE_PREFER_IPV6: &str = "SERVICE_PREFER_IPV6";
+    /// Service group (defaults to [`default_group`](crate::default_group)).
+    pub const NACOS_GROUP: &str = "NACOS_GROUP";
+    /// Instance weight (defaults to [`DEFAULT_WEIGHT`](crate::DEFAULT_WEIGHT)).
+    pub const SERVICE_WEIGHT: &str = "SERVICE_WEIGHT";
+    /// Whether the instance is ephemeral, `true` or `false` (defaults to `true`).
+    pub const SERVICE_EPHEMERAL: &str = "SERVICE_EPHEMERAL";
+    /// Cluster the instance belongs to (defaults to nacos's `DEFAULT` cluster).
+    pub const SERVICE_CLUSTER: &str = "SERVICE_CLUSTER";
+    /// Transport clients should use: `grpc`, `grpcs`, `http` or `https` (unset by default).
+    pub const SERVICE_PROTOCOL: &str = "SERVICE_PROTOCOL";
+    /// Extra instance metadata as a JSON object of string values, e.g.
+    /// `{"version":"2","zone":"a"}`. Values are taken literally unless
+    /// [`SERVICE_METADATA_EXPAND`] is set.
+    pub const SERVICE_METADATA: &str = "SERVICE_METADATA";
+    /// Whether `${NAME}` references in [`SERVICE_METADATA`] values are expanded from other
+    /// variables, `true` or `false` (defaults to `false`), like
+    /// [`expand_metadata_env`](crate::ServiceConfigBuilder::expand_metadata_env) on the builder.
+    pub const SERVICE_METADATA_EXPAND: &str = "SERVICE_METADATA_EXPAND";
 }
 
 /// Full configuration required to register a service instance.
 ///
-/// Build one via [`ServiceConfig::builder`] or [`ServiceConfig::from_env`].
+/// Build one via [`ServiceConfig::builder`] or [`ServiceConfig::from_env`]. Processes that
+/// only look up other services can skip the instance fields via
+/// [`discovery_only`](ServiceConfigBuilder::discovery_only).
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
 pub struct ServiceConfig {
-    /// Nacos server address, formatted as `host:port`.
+    /// Nacos server address, formatted as `host:port`; a Nacos cluster is given as a
+    /// comma-separated list such as `a:8848,b:8848`. May be empty when
+    /// [`endpoint`](Self::endpoint) is set.
+    #[cfg_attr(feature = "serde", serde(default))]
     pub nacos_addr: String,
-    /// Nacos namespace id.
+    /// Address server the Nacos client fetches its server list from, taking precedence over
+    /// [`nacos_addr`](Self::nacos_addr). See [`ServiceConfigBuilder::endpoint`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub endpoint: Option<String>,
+    /// Nacos namespace id; empty means Nacos's public namespace.
     pub namespace: String,
     /// Service name.
     pub service_name: String,
-    /// Service group, defaults to [`DEFAULT_GROUP`].
+    /// Service group, defaults to [`default_group`].
     pub group: String,
+    /// Further groups the instance is registered in alongside [`group`](Self::group); see
+    /// [`ServiceConfigBuilder::extra_groups`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub extra_groups: Vec<String>,
     /// Advertised host (IP or hostname) registered to Nacos.
     pub service_host: String,
     /// Advertised port registered to Nacos.
@@ -51,10 +150,45 @@ pub struct ServiceConfig {
     pub weight: f64,
     /// Whether the instance is ephemeral, defaults to `true`.
     pub ephemeral: bool,
+    /// Whether the instance accepts traffic once registered, defaults to `true`. A disabled
+    /// instance stays visible in the Nacos console but is skipped by discovery.
+    #[cfg_attr(feature = "serde", serde(default = "default_enabled"))]
+    pub enabled: bool,
+    /// Cluster the instance belongs to; `None` uses nacos's `DEFAULT` cluster.
+    pub cluster_name: Option<String>,
+    /// Explicit instance id; `None` lets Nacos generate one. See
+    /// [`ServiceConfigBuilder::instance_id`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub instance_id: Option<String>,
     /// Auth credentials (`username`, `password`); both must be provided or neither.
     pub auth: Option<(String, String)>,
-    /// Extra metadata. [`META_GRPC_PORT`] is auto-populated with the port unless the user
-    /// supplies their own value.
+    /// Access-key auth credentials (`access_key`, `secret_key`); requires the `aliyun-auth`
+    /// feature and excludes [`auth`](Self::auth).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub access_key: Option<(String, String)>,
+    /// Upper bound for each register/deregister call; `None` waits indefinitely.
+    pub timeout: Option<Duration>,
+    /// Interval of the watchdog that re-registers the instance if it vanishes from the
+    /// registry (e.g. after a Nacos restart); `None` disables it.
+    pub keepalive: Option<Duration>,
+    /// How long instance lookups are served from cache; `None` queries the registry every
+    /// time. See [`ServiceConfigBuilder::cache_ttl`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub cache_ttl: Option<Duration>,
+    /// Upper bound of the random delay before the first registration; `None` registers
+    /// immediately. See [`ServiceConfigBuilder::register_jitter`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub register_jitter: Option<Duration>,
+    /// Client application name reported to Nacos; `None` uses `service_name`.
+    pub app_name: Option<String>,
+    /// Whether this configuration only discovers other services. When set, the instance
+    /// fields (`service_name`, `service_host`, `service_port`, ...) may be left empty and the
+    /// manager refuses to [`register`](crate::ServiceManager::register).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub discovery_only: bool,
+    /// Extra metadata. [`META_GRPC_PORT`] (or the key set via
+    /// [`grpc_port_key`](ServiceConfigBuilder::grpc_port_key)) is auto-populated with the port
+    /// unless the user supplies their own value.
     pub metadata: HashMap<String, String>,
 }
 
@@ -64,38 +198,278 @@ impl ServiceConfig {
         ServiceConfigBuilder::default()
     }
 
+    /// Check the configuration for mistakes without contacting Nacos.
+    ///
+    /// [`ServiceConfigBuilder::build`] and [`ServiceManager::new`](crate::ServiceManager::new)
+    /// already run this; call it directly as a dry run (e.g. in CI smoke tests) or after
+    /// editing the public fields by hand.
+    pub fn validate(&self) -> Result<()> {
+        match &self.endpoint {
+            Some(endpoint) => ensure_non_empty(endpoint, "endpoint")?,
+            None => ensure_non_empty(&self.nacos_addr, "nacos_addr")?,
+        }
+        // With an endpoint the server list comes from the address server instead.
+        if !self.nacos_addr.trim().is_empty() {
+            for addr in self.nacos_addr.split(',') {
+                validate_host_port(addr.trim(), "nacos_addr")?;
+            }
+        }
+        if self.access_key.is_some() {
+            if !cfg!(feature = "aliyun-auth") {
+                return Err(Error::invalid_config(
+                    "`access_key` requires the `aliyun-auth` feature",
+                ));
+            }
+            if self.auth.is_some() {
+                return Err(Error::invalid_config(
+                    "`auth` and `access_key` are mutually exclusive",
+                ));
+            }
+        }
+        self.validate_instance()
+    }
+
+    /// The Nacos server list in the comma-separated form expected by nacos-sdk, with
+    /// whitespace around each entry removed.
+    pub(crate) fn nacos_server_list(&self) -> String {
+        self.nacos_addr
+            .split(',')
+            .map(str::trim)
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// The application name reported to Nacos: [`app_name`](Self::app_name) if set, otherwise
+    /// the service name. `None` when neither is available (discovery-only without a name).
+    pub(crate) fn client_app_name(&self) -> Option<&str> {
+        self.app_name
+            .as_deref()
+            .or(Some(self.service_name.as_str()))
+            .filter(|name| !name.trim().is_empty())
+    }
+
+    /// The part of [`validate`](Self::validate) that describes the instance itself, as
+    /// opposed to the Nacos connection.
+    pub(crate) fn validate_instance(&self) -> Result<()> {
+        ensure_non_empty(&self.group, "group")?;
+        if self.timeout == Some(Duration::ZERO) {
+            return Err(Error::invalid_config(
+                "invalid `timeout` = `0s`: must be positive",
+            ));
+        }
+        if self.keepalive == Some(Duration::ZERO) {
+            return Err(Error::invalid_config(
+                "invalid `keepalive` = `0s`: must be positive",
+            ));
+        }
+        if self.cache_ttl == Some(Duration::ZERO) {
+            return Err(Error::invalid_config(
+                "invalid `cache_ttl` = `0s`: must be positive",
+            ));
+        }
+        if self.discovery_only {
+            return Ok(());
+        }
+        if let Some(instance_id) = &self.instance_id {
+            ensure_non_empty(instance_id, "instance_id")?;
+        }
+        ensure_non_empty(&self.service_name, "service_name")?;
+        ensure_non_empty(&self.service_host, "service_host")?;
+        Port::new(self.service_port, "service_port")?;
+        validate_weight(self.weight)?;
+        for (i, group) in self.extra_groups.iter().enumerate() {
+            ensure_non_empty(group, "extra_groups")?;
+            if *group == self.group || self.extra_groups[..i].contains(group) {
+                return Err(Error::invalid_config(format!(
+                    "duplicate group `{group}` in `extra_groups`"
+                )));
+            }
+        }
+        if let Some(protocol) = self.metadata.get(META_PROTOCOL) {
+            validate_protocol(protocol)?;
+        }
+        Ok(())
+    }
+
     /// Load configuration from environment variables.
     ///
-    /// Required: [`NACOS_ADDR`](env_keys::NACOS_ADDR), [`NACOS_NAMESPACE`](env_keys::NACOS_NAMESPACE),
-    /// [`SERVICE_ADDR`](env_keys::SERVICE_ADDR), [`SERVICE_NAME`](env_keys::SERVICE_NAME).
+    /// Required: [`NACOS_ADDR`](env_keys::NACOS_ADDR) (unless
+    /// [`NACOS_ENDPOINT`](env_keys::NACOS_ENDPOINT) is set),
+    /// [`SERVICE_NAME`](env_keys::SERVICE_NAME), and either
+    /// [`SERVICE_ADDR`](env_keys::SERVICE_ADDR) or [`SERVICE_PORT`](env_keys::SERVICE_PORT).
     ///
-    /// Optional: [`SERVICE_HOST`](env_keys::SERVICE_HOST) (falls back to the local IP),
+    /// Optional: [`NACOS_NAMESPACE`](env_keys::NACOS_NAMESPACE) (falls back to the public
+    /// namespace), [`SERVICE_HOST`](env_keys::SERVICE_HOST) (falls back to the address of
+    /// [`SERVICE_IFACE`](env_keys::SERVICE_IFACE) if set, then to the local IP, IPv6 with
+    /// [`SERVICE_PREFER_IPV6`](env_keys::SERVICE_PREFER_IPV6)),
+    /// [`NACOS_GROUP`](env_keys::NACOS_GROUP) (falls back to [`default_group`]),
+    /// [`SERVICE_WEIGHT`](env_keys::SERVICE_WEIGHT) (falls back to [`DEFAULT_WEIGHT`]),
+    /// [`SERVICE_EPHEMERAL`](env_keys::SERVICE_EPHEMERAL) (falls back to `true`),
+    /// [`SERVICE_CLUSTER`](env_keys::SERVICE_CLUSTER) (falls back to nacos's `DEFAULT` cluster),
+    /// [`SERVICE_PROTOCOL`](env_keys::SERVICE_PROTOCOL) (unset by default),
+    /// [`SERVICE_METADATA`](env_keys::SERVICE_METADATA) (merged into the metadata),
+    /// [`SERVICE_METADATA_EXPAND`](env_keys::SERVICE_METADATA_EXPAND) (falls back to `false`),
     /// [`NACOS_USERNAME`](env_keys::NACOS_USERNAME) + [`NACOS_PASSWORD`](env_keys::NACOS_PASSWORD)
-    /// (both must be present, or both absent).
+    /// and [`NACOS_AK`](env_keys::NACOS_AK) + [`NACOS_SK`](env_keys::NACOS_SK)
+    /// (each pair both present, or both absent).
     pub fn from_env() -> Result<Self> {
-        let nacos_addr = read_env(env_keys::NACOS_ADDR)?;
-        let namespace = read_env(env_keys::NACOS_NAMESPACE)?;
-        let service_addr = read_env(env_keys::SERVICE_ADDR)?;
-        let service_name = read_env(env_keys::SERVICE_NAME)?;
-        let service_host = env::var(env_keys::SERVICE_HOST).ok();
-        let username = env::var(env_keys::NACOS_USERNAME).ok();
-        let password = env::var(env_keys::NACOS_PASSWORD).ok();
+        Self::from_env_with_prefix("")
+    }
+
+    /// Like [`from_env`](Self::from_env), but every variable name is prefixed with `prefix`,
+    /// e.g. `from_env_with_prefix("MYAPP_")` reads `MYAPP_NACOS_ADDR`.
+    pub fn from_env_with_prefix(prefix: &str) -> Result<Self> {
+        Self::from_lookup(prefix, |name| env::var(name))
+    }
+
+    /// Load configuration through an arbitrary variable lookup; see [`from_env`](Self::from_env).
+    fn from_lookup<F>(prefix: &str, lookup: F) -> Result<Self>
+    where
+        F: Fn(&str) -> std::result::Result<String, env::VarError>,
+    {
+        let key = |name: &str| format!("{prefix}{name}");
+        let get = |name: &str| lookup(&key(name)).ok();
+        let read = |name: &str| {
+            let name = key(name);
+            lookup(&name).map_err(|source| Error::env(name, source))
+        };
+        let endpoint = get(env_keys::NACOS_ENDPOINT);
+        let nacos_addr = match endpoint {
+            Some(_) => get(env_keys::NACOS_ADDR).unwrap_or_default(),
+            None => read(env_keys::NACOS_ADDR)?,
+        };
+        let namespace = get(env_keys::NACOS_NAMESPACE);
+        let service_addr = get(env_keys::SERVICE_ADDR);
+        let service_port = get(env_keys::SERVICE_PORT);
+        let service_name = read(env_keys::SERVICE_NAME)?;
+        let service_host = get(env_keys::SERVICE_HOST);
+        let service_iface = get(env_keys::SERVICE_IFACE);
+        let prefer_ipv6 = get(env_keys::SERVICE_PREFER_IPV6);
+        let group = get(env_keys::NACOS_GROUP);
+        let weight = get(env_keys::SERVICE_WEIGHT);
+        let ephemeral = get(env_keys::SERVICE_EPHEMERAL);
+        let cluster_name = get(env_keys::SERVICE_CLUSTER);
+        let protocol = get(env_keys::SERVICE_PROTOCOL);
+        let metadata = get(env_keys::SERVICE_METADATA);
+        let expand_metadata_refs = get(env_keys::SERVICE_METADATA_EXPAND);
+        let username = get(env_keys::NACOS_USERNAME);
+        let password = get(env_keys::NACOS_PASSWORD);
+        let access_key = get(env_keys::NACOS_AK);
+        let secret_key = get(env_keys::NACOS_SK);
 
         let mut builder = Self::builder()
             .nacos_addr(nacos_addr)
-            .namespace(namespace)
-            .service_name(service_name)
-            .bind_addr(service_addr)?;
+            .service_name(service_name);
+        if let Some(endpoint) = endpoint {
+            builder = builder.endpoint(endpoint);
+        }
+        if let Some(namespace) = namespace {
+            builder = builder.namespace(namespace);
+        }
+        builder = match (service_port, service_addr) {
+            (Some(port), _) => {
+                // Only parsed here; the range is checked once, by `validate`.
+                builder.service_port(port.trim().parse().map_err(|_| {
+                    Error::invalid_config(format!(
+                        "invalid `{}` = `{port}`: bad port",
+                        key(env_keys::SERVICE_PORT)
+                    ))
+                })?)
+            }
+            (None, Some(addr)) => builder.bind_addr(addr)?,
+            (None, None) => {
+                return Err(Error::env(
+                    key(env_keys::SERVICE_ADDR),
+                    env::VarError::NotPresent,
+                ));
+            }
+        };
         if let Some(host) = service_host {
             builder = builder.service_host(host);
         }
+        if let Some(prefer_ipv6) = prefer_ipv6 {
+            let prefer_ipv6: bool = prefer_ipv6.parse().map_err(|_| {
+                Error::invalid_config(format!(
+                    "invalid `{}` = `{prefer_ipv6}` (expect true or false)",
+                    key(env_keys::SERVICE_PREFER_IPV6)
+                ))
+            })?;
+            if prefer_ipv6 {
+                builder = builder.host_strategy(HostStrategy::LocalIpv6);
+            }
+        }
+        if let Some(iface) = service_iface {
+            builder = builder.host_strategy(HostStrategy::Interface(iface));
+        }
+        if let Some(group) = group {
+            builder = builder.group(group);
+        }
+        if let Some(weight) = weight {
+            let weight = weight.parse().map_err(|_| {
+                Error::invalid_config(format!(
+                    "invalid `{}` = `{weight}`",
+                    key(env_keys::SERVICE_WEIGHT)
+                ))
+            })?;
+            builder = builder.weight(weight);
+        }
+        if let Some(ephemeral) = ephemeral {
+            let ephemeral = ephemeral.parse().map_err(|_| {
+                Error::invalid_config(format!(
+                    "invalid `{}` = `{ephemeral}` (expect true or false)",
+                    key(env_keys::SERVICE_EPHEMERAL)
+                ))
+            })?;
+            builder = builder.ephemeral(ephemeral);
+        }
+        if let Some(cluster_name) = cluster_name {
+            builder = builder.cluster_name(cluster_name);
+        }
+        if let Some(protocol) = protocol {
+            builder = builder.protocol(protocol);
+        }
+        if let Some(metadata) = metadata {
+            let mut metadata: HashMap<String, String> =
+                serde_json::from_str(&metadata).map_err(|err| {
+                    Error::invalid_config(format!(
+                        "invalid `{}`: expect a JSON object of string values: {err}",
+                        key(env_keys::SERVICE_METADATA)
+                    ))
+                })?;
+            let expand = match expand_metadata_refs {
+                Some(expand) => expand.parse().map_err(|_| {
+                    Error::invalid_config(format!(
+                        "invalid `{}` = `{expand}` (expect true or false)",
+                        key(env_keys::SERVICE_METADATA_EXPAND)
+                    ))
+                })?,
+                None => false,
+            };
+            if expand {
+                expand_metadata(&mut metadata, |name| lookup(name).ok())?;
+            }
+            builder = builder.metadata_all(metadata);
+        }
         match (username, password) {
             (Some(u), Some(p)) => builder = builder.auth(u, p),
             (None, None) => {}
             _ => {
-                return Err(Error::invalid_config(
-                    "NACOS_USERNAME and NACOS_PASSWORD must be provided together",
-                ));
+                return Err(Error::invalid_config(format!(
+                    "{} and {} must be provided together",
+                    key(env_keys::NACOS_USERNAME),
+                    key(env_keys::NACOS_PASSWORD)
+                )));
+            }
+        }
+        match (access_key, secret_key) {
+            (Some(ak), Some(sk)) => builder = builder.access_key(ak, sk),
+            (None, None) => {}
+            _ => {
+                return Err(Error::invalid_config(format!(
+                    "{} and {} must be provided together",
+                    key(env_keys::NACOS_AK),
+                    key(env_keys::NACOS_SK)
+                )));
             }
         }
         builder.build()
@@ -103,28 +477,83 @@ impl ServiceConfig {
 }
 
 /// Fluent builder for [`ServiceConfig`].
+///
+/// # Examples
+///
+/// ```
+/// use ez_rust_discovery::ServiceConfig;
+///
+/// let config = ServiceConfig::builder()
+///     .nacos_addr("127.0.0.1:8848")
+///     .namespace("public")
+///     .service_name("payment-service")
+///     .service_host("10.0.0.1")
+///     .bind_addr("0.0.0.0:9000")?
+///     .build()?;
+/// assert_eq!(config.service_port, 9000);
+/// # Ok::<(), ez_rust_discovery::Error>(())
+/// ```
+///
+/// With the `serde` feature the builder can also be deserialized from a config file; every
+/// field is optional, so omitted values can still be filled in through the setters before
+/// calling [`build`](Self::build).
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
 pub struct ServiceConfigBuilder {
     nacos_addr: Option<String>,
+    endpoint: Option<String>,
     namespace: Option<String>,
     service_name: Option<String>,
     group: Option<String>,
+    extra_groups: Vec<String>,
     service_host: Option<String>,
     service_port: Option<u16>,
     weight: Option<f64>,
     ephemeral: Option<bool>,
+    enabled: Option<bool>,
+    cluster_name: Option<String>,
+    instance_id: Option<String>,
     auth: Option<(String, String)>,
+    access_key: Option<(String, String)>,
+    timeout: Option<Duration>,
+    keepalive: Option<Duration>,
+    cache_ttl: Option<Duration>,
+    register_jitter: Option<Duration>,
+    app_name: Option<String>,
+    discovery_only: bool,
+    grpc_port_key: Option<String>,
+    http_port: Option<u16>,
+    expand_metadata_env: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    host_strategy: HostStrategy,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    host_resolver: Option<HostResolver>,
     metadata: HashMap<String, String>,
 }
 
 impl ServiceConfigBuilder {
-    /// Set the Nacos server address (`host:port`).
+    /// Set the Nacos server address (`host:port`), or a comma-separated list of them for a
+    /// Nacos cluster.
     pub fn nacos_addr(mut self, addr: impl Into<String>) -> Self {
         self.nacos_addr = Some(addr.into());
         self
     }
 
-    /// Set the namespace id.
+    /// Resolve the Nacos server list from an address server instead of a fixed
+    /// [`nacos_addr`](Self::nacos_addr), which then becomes optional.
+    ///
+    /// A full URL such as `http://addr:8080/nacos/serverlist` is used as-is; a bare host
+    /// (`addr` or `addr:9090`) gets the default `/nacos/serverlist` path and port 8080. The
+    /// connection to the servers themselves is gRPC, so a reverse-proxy context path cannot
+    /// be expressed here.
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// Set the namespace id. Leaving it unset, or setting it to `""`, selects Nacos's public
+    /// namespace.
     pub fn namespace(mut self, ns: impl Into<String>) -> Self {
         self.namespace = Some(ns.into());
         self
@@ -136,18 +565,51 @@ impl ServiceConfigBuilder {
         self
     }
 
-    /// Set the service group (defaults to [`DEFAULT_GROUP`]).
+    /// Set the service group (defaults to [`default_group`], i.e. [`DEFAULT_GROUP`] unless
+    /// overridden via [`set_default_group`]).
     pub fn group(mut self, group: impl Into<String>) -> Self {
         self.group = Some(group.into());
         self
     }
 
+    /// Also register the instance in each of `groups`, e.g. for a shared utility service
+    /// that several groups discover. [`register`](crate::ServiceManager::register) and
+    /// [`deregister`](crate::ServiceManager::deregister) then cover every group, while
+    /// lookups keep defaulting to [`group`](Self::group).
+    pub fn extra_groups<I, S>(mut self, groups: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.extra_groups.extend(groups.into_iter().map(Into::into));
+        self
+    }
+
     /// Set the advertised host registered to Nacos (defaults to the local IP).
     pub fn service_host(mut self, host: impl Into<String>) -> Self {
         self.service_host = Some(host.into());
         self
     }
 
+    /// Choose how the advertised host is detected when [`service_host`](Self::service_host) is
+    /// not set (defaults to [`HostStrategy::LocalIp`]).
+    pub fn host_strategy(mut self, strategy: HostStrategy) -> Self {
+        self.host_strategy = strategy;
+        self
+    }
+
+    /// Compute the advertised host at [`build`](Self::build) time, e.g. by querying a cloud
+    /// metadata service, when [`service_host`](Self::service_host) is not set. Takes
+    /// precedence over [`host_strategy`](Self::host_strategy); errors are returned from
+    /// `build` as-is.
+    pub fn host_resolver<F>(mut self, resolver: F) -> Self
+    where
+        F: Fn() -> Result<String> + Send + Sync + 'static,
+    {
+        self.host_resolver = Some(HostResolver::new(resolver));
+        self
+    }
+
     /// Set the advertised port registered to Nacos.
     pub fn service_port(mut self, port: u16) -> Self {
         self.service_port = Some(port);
@@ -158,47 +620,189 @@ impl ServiceConfigBuilder {
     /// advertised host comes from [`service_host`](Self::service_host) or the local IP).
     ///
     /// Provided for compatibility with the `SERVICE_ADDR` environment variable convention.
+    /// A bare port such as `9000` is accepted too. IPv6 literals must be bracketed, e.g.
+    /// `[::1]:8080`. Anything else fails with [`Error::InvalidServiceAddr`].
     pub fn bind_addr(mut self, addr: impl AsRef<str>) -> Result<Self> {
         let addr = addr.as_ref();
-        let (host, port) = addr.rsplit_once(':').ok_or_else(|| {
-            Error::invalid_config(format!("invalid bind address `{addr}` (expect host:port)"))
-        })?;
-        if host.is_empty() {
-            return Err(Error::invalid_config(format!(
-                "invalid bind address `{addr}`: empty host"
-            )));
+        if let Ok(socket) = addr.parse::<SocketAddr>() {
+            self.service_port = Some(socket.port());
+            return Ok(self);
         }
-        let port: u16 = port.parse().map_err(|_| {
-            Error::invalid_config(format!("invalid bind address `{addr}`: bad port"))
-        })?;
+        if let Ok(port) = addr.parse::<u16>() {
+            self.service_port = Some(port);
+            return Ok(self);
+        }
+        let (_, port) =
+            resolve_endpoint(addr).map_err(|_| Error::InvalidServiceAddr(addr.to_string()))?;
         self.service_port = Some(port);
         Ok(self)
     }
 
-    /// Set the instance weight.
+    /// Set the instance weight; must be a finite, non-negative number.
     pub fn weight(mut self, weight: f64) -> Self {
         self.weight = Some(weight);
         self
     }
 
     /// Set whether the instance is ephemeral (defaults to `true`).
+    ///
+    /// Ephemeral instances are kept alive by client heartbeats and dropped by Nacos once they
+    /// stop. Persistent ones (`false`) stay registered until explicitly deregistered and are
+    /// health-checked by the server according to the cluster's health-check settings, so a
+    /// [`keepalive`](Self::keepalive) watchdog does not run for them; `build` warns when both
+    /// are configured.
+    ///
+    /// No other instance fields change for persistent instances: nacos-sdk picks the
+    /// persistent register/deregister calls from this flag alone, and the `healthy` flag sent
+    /// at registration is only the initial state that the server's health checks take over.
     pub fn ephemeral(mut self, ephemeral: bool) -> Self {
         self.ephemeral = Some(ephemeral);
         self
     }
 
+    /// Set whether the instance is registered enabled (defaults to `true`). Register it
+    /// disabled to stage a blue/green deployment, then flip it with
+    /// [`ServiceManager::set_enabled`](crate::ServiceManager::set_enabled).
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = Some(enabled);
+        self
+    }
+
+    /// Set the cluster the instance belongs to (defaults to nacos's `DEFAULT` cluster).
+    pub fn cluster_name(mut self, cluster_name: impl Into<String>) -> Self {
+        self.cluster_name = Some(cluster_name.into());
+        self
+    }
+
+    /// Register the instance under a fixed id instead of one generated by Nacos, for a
+    /// stable identity across restarts.
+    ///
+    /// The id must be unique among live instances of the service: a restarted process that
+    /// reuses it takes over the old registration, and two processes sharing one id overwrite
+    /// each other. Whether the server honours client-supplied ids depends on the Nacos
+    /// version and the service's id generator.
+    pub fn instance_id(mut self, instance_id: impl Into<String>) -> Self {
+        self.instance_id = Some(instance_id.into());
+        self
+    }
+
+    /// Place the instance in `zone` for zone-aware routing: sets both the
+    /// [`cluster_name`](Self::cluster_name) and the [`META_ZONE`] metadata entry, so the two
+    /// cannot disagree.
+    pub fn zone(self, zone: impl Into<String>) -> Self {
+        let zone = zone.into();
+        self.cluster_name(zone.clone()).metadata(META_ZONE, zone)
+    }
+
+    /// Advertise the transport clients should dial the instance with under the
+    /// [`META_PROTOCOL`] metadata key: one of `grpc`, `grpcs`, `http` or `https`. Any other
+    /// value fails [`build`](Self::build).
+    pub fn protocol(self, protocol: impl Into<String>) -> Self {
+        self.metadata(META_PROTOCOL, protocol)
+    }
+
+    /// Set the application name the Nacos client reports, shown in the Nacos console
+    /// (defaults to the service name).
+    pub fn app_name(mut self, app_name: impl Into<String>) -> Self {
+        self.app_name = Some(app_name.into());
+        self
+    }
+
+    /// Build a configuration that only discovers other services and never registers one.
+    ///
+    /// `service_name`, `service_port` and `service_host` become optional (the local IP is not
+    /// detected), and [`ServiceManager::register`](crate::ServiceManager::register) fails with
+    /// [`Error::InvalidConfig`].
+    pub fn discovery_only(mut self) -> Self {
+        self.discovery_only = true;
+        self
+    }
+
     /// Set the auth credentials.
     pub fn auth(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
         self.auth = Some((username.into(), password.into()));
         self
     }
 
-    /// Insert a single metadata entry.
+    /// Authenticate with an access key / secret key pair instead of a username and password,
+    /// as cloud-hosted Nacos requires. Needs the `aliyun-auth` feature.
+    pub fn access_key(
+        mut self,
+        access_key: impl Into<String>,
+        secret_key: impl Into<String>,
+    ) -> Self {
+        self.access_key = Some((access_key.into(), secret_key.into()));
+        self
+    }
+
+    /// Bound every register/deregister call by `timeout`; exceeding it fails with
+    /// [`Error::Timeout`]. Without a timeout a wedged Nacos can stall the call indefinitely.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Check every `interval` that the registered instance is still listed and re-register it
+    /// if not, so it recovers on its own after Nacos restarts or the client reconnects.
+    ///
+    /// The watchdog starts with [`ServiceManager::register`](crate::ServiceManager::register)
+    /// and stops with [`deregister`](crate::ServiceManager::deregister); see
+    /// [`spawn_keepalive`](crate::ServiceManager::spawn_keepalive) for a manually driven one.
+    /// It is skipped for persistent instances, which Nacos never drops; `build` warns when
+    /// combined with `ephemeral(false)`.
+    pub fn keepalive(mut self, interval: Duration) -> Self {
+        self.keepalive = Some(interval);
+        self
+    }
+
+    /// Serve [`get_instances`](crate::ServiceManager::get_instances) and the `select_*`
+    /// helpers from a per-service cache that stays fresh for `ttl`.
+    ///
+    /// The first lookup of a service queries the registry. Once an entry is older than `ttl`
+    /// the next lookup still returns it but refreshes it in the background, so hot paths never
+    /// wait on the registry. [`subscribe`](crate::ServiceManager::subscribe)d services drop
+    /// their entry whenever a change is pushed.
+    pub fn cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Delay the first [`register`](crate::ServiceManager::register) by a random duration
+    /// between zero and `max`, so a fleet started all at once does not hit Nacos in one burst.
+    ///
+    /// Only the first registration of a manager waits; retries and re-registrations do not.
+    pub fn register_jitter(mut self, max: Duration) -> Self {
+        self.register_jitter = Some(max);
+        self
+    }
+
+    /// Override the metadata key the port is published under (defaults to [`META_GRPC_PORT`]).
+    pub fn grpc_port_key(mut self, key: impl Into<String>) -> Self {
+        self.grpc_port_key = Some(key.into());
+        self
+    }
+
+    /// Advertise a separate HTTP port under the [`META_HTTP_PORT`] metadata key.
+    pub fn http_port(mut self, port: u16) -> Self {
+        self.http_port = Some(port);
+        self
+    }
+
+    /// Insert a single metadata entry. User-supplied keys win over auto-populated ones such as
+    /// [`META_GRPC_PORT`].
     pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
         self.metadata.insert(key.into(), value.into());
         self
     }
 
+    /// Expand `${NAME}` references in metadata values from the process environment when
+    /// building, e.g. `version=${BUILD_VERSION}`. A reference to an unset variable fails the
+    /// build with [`Error::InvalidConfig`].
+    pub fn expand_metadata_env(mut self) -> Self {
+        self.expand_metadata_env = true;
+        self
+    }
+
     /// Insert multiple metadata entries at once.
     pub fn metadata_all<I, K, V>(mut self, entries: I) -> Self
     where
@@ -213,41 +817,148 @@ impl ServiceConfigBuilder {
 
     /// Validate and build the [`ServiceConfig`].
     pub fn build(self) -> Result<ServiceConfig> {
-        let nacos_addr = require(self.nacos_addr, "nacos_addr")?;
-        validate_host_port(&nacos_addr, "nacos_addr")?;
-        let namespace = require(self.namespace, "namespace")?;
-        let service_name = require(self.service_name, "service_name")?;
-        let service_port = require(self.service_port, "service_port")?;
-        let service_host = match self.service_host {
-            Some(h) => h,
-            None => local_ip_address::local_ip()?.to_string(),
+        let nacos_addr = match self.endpoint {
+            Some(_) => self.nacos_addr.unwrap_or_default(),
+            None => require(self.nacos_addr, "nacos_addr")?,
+        };
+        let namespace = self.namespace.unwrap_or_default();
+        let mut user_metadata = self.metadata;
+        if self.expand_metadata_env {
+            expand_metadata(&mut user_metadata, |name| env::var(name).ok())?;
+        }
+        let (service_name, service_port, service_host, metadata) = if self.discovery_only {
+            (
+                self.service_name.unwrap_or_default(),
+                self.service_port.unwrap_or_default(),
+                self.service_host.unwrap_or_default(),
+                user_metadata,
+            )
+        } else {
+            let service_name = require(self.service_name, "service_name")?;
+            // Range-checked once, by the `validate` call below.
+            let service_port = require(self.service_port, "service_port")?;
+            // Only probe the network interfaces when no host was given.
+            let service_host = match (self.service_host, &self.host_resolver) {
+                (Some(h), _) => h,
+                (None, Some(resolver)) => resolver.resolve()?,
+                (None, None) => self.host_strategy.resolve()?,
+            };
+            let grpc_port_key = match self.grpc_port_key {
+                Some(key) => require_non_empty(Some(key), "grpc_port_key")?,
+                None => META_GRPC_PORT.to_string(),
+            };
+            let mut metadata = user_metadata;
+            metadata
+                .entry(grpc_port_key)
+                .or_insert_with(|| service_port.to_string());
+            if let Some(http_port) = self.http_port {
+                let http_port = Port::new(http_port, "http_port")?;
+                metadata
+                    .entry(META_HTTP_PORT.to_string())
+                    .or_insert_with(|| http_port.to_string());
+            }
+            (service_name, service_port, service_host, metadata)
         };
-        let mut metadata = self.metadata;
-        metadata
-            .entry(META_GRPC_PORT.to_string())
-            .or_insert_with(|| service_port.to_string());
 
-        Ok(ServiceConfig {
+        let config = ServiceConfig {
             nacos_addr,
+            endpoint: self.endpoint,
             namespace,
             service_name,
-            group: self.group.unwrap_or_else(|| DEFAULT_GROUP.to_string()),
+            group: self.group.unwrap_or_else(default_group),
+            extra_groups: self.extra_groups,
             service_host,
             service_port,
             weight: self.weight.unwrap_or(DEFAULT_WEIGHT),
             ephemeral: self.ephemeral.unwrap_or(true),
+            enabled: self.enabled.unwrap_or(true),
+            cluster_name: self.cluster_name,
+            instance_id: self.instance_id,
             auth: self.auth,
+            access_key: self.access_key,
+            timeout: self.timeout,
+            keepalive: self.keepalive,
+            cache_ttl: self.cache_ttl,
+            register_jitter: self.register_jitter,
+            app_name: self.app_name,
+            discovery_only: self.discovery_only,
             metadata,
-        })
+        };
+        config.validate()?;
+        if config.keepalive.is_some() && !config.ephemeral && !config.discovery_only {
+            warn!(
+                service = %config.service_name,
+                "keepalive has no effect on persistent instances; the watchdog will not run"
+            );
+        }
+        Ok(config)
+    }
+}
+
+/// Serde default for [`ServiceConfig::enabled`].
+#[cfg(feature = "serde")]
+fn default_enabled() -> bool {
+    true
+}
+
+/// Ensure a [`META_PROTOCOL`] value is one of the known transports.
+pub(crate) fn validate_protocol(protocol: &str) -> Result<()> {
+    if !PROTOCOLS.contains(&protocol) {
+        return Err(Error::invalid_config(format!(
+            "invalid `{META_PROTOCOL}` = `{protocol}`: expect one of {}",
+            PROTOCOLS.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Ensure an instance weight is finite and non-negative.
+pub(crate) fn validate_weight(weight: f64) -> Result<()> {
+    if !weight.is_finite() || weight < 0.0 {
+        return Err(Error::invalid_config(format!(
+            "invalid `weight` = `{weight}`: must be a finite, non-negative number"
+        )));
+    }
+    Ok(())
+}
+
+/// Expand `${NAME}` references in every value of `metadata` through `lookup`.
+fn expand_metadata<F>(metadata: &mut HashMap<String, String>, lookup: F) -> Result<()>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    for (key, value) in metadata.iter_mut() {
+        *value = expand_vars(value, &lookup).map_err(|reason| {
+            Error::invalid_config(format!("invalid metadata `{key}` = `{value}`: {reason}"))
+        })?;
     }
+    Ok(())
 }
 
-/// Read an environment variable, turning a miss into [`Error::Env`].
-fn read_env(name: &str) -> Result<String> {
-    env::var(name).map_err(|source| Error::Env {
-        name: name.to_string(),
-        source,
-    })
+/// Replace each `${NAME}` in `value` with `lookup(NAME)`; the error is a short reason to be
+/// embedded by the caller.
+fn expand_vars<F>(value: &str, lookup: F) -> std::result::Result<String, String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| "unterminated `${`".to_string())?;
+        let name = &after[..end];
+        if name.is_empty() {
+            return Err("empty variable reference `${}`".to_string());
+        }
+        let resolved = lookup(name).ok_or_else(|| format!("unresolved variable `{name}`"))?;
+        expanded.push_str(&resolved);
+        rest = &after[end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
 }
 
 /// Ensure a required builder field is present.
@@ -255,20 +966,62 @@ fn require<T>(value: Option<T>, field: &str) -> Result<T> {
     value.ok_or_else(|| Error::invalid_config(format!("missing required field `{field}`")))
 }
 
+/// Ensure a required string field is present and not blank.
+fn require_non_empty(value: Option<String>, field: &str) -> Result<String> {
+    let value = require(value, field)?;
+    ensure_non_empty(&value, field)?;
+    Ok(value)
+}
+
+/// Ensure a string field is not blank.
+fn ensure_non_empty(value: &str, field: &str) -> Result<()> {
+    if value.trim().is_empty() {
+        return Err(Error::invalid_config(format!(
+            "`{field}` must not be empty"
+        )));
+    }
+    Ok(())
+}
+
 /// Validate that `addr` looks like `host:port`, where `host` may be a hostname/IP and `port`
 /// fits in a `u16`.
 fn validate_host_port(addr: &str, field: &str) -> Result<()> {
-    let (host, port) = addr.rsplit_once(':').ok_or_else(|| {
-        Error::invalid_config(format!("invalid `{field}` = `{addr}` (expect host:port)"))
-    })?;
+    split_host_port(addr)
+        .map(|_| ())
+        .map_err(|reason| Error::invalid_config(format!("invalid `{field}` = `{addr}`: {reason}")))
+}
+
+/// Split a `host:port` address into its host and port.
+///
+/// IPv6 hosts must be bracketed (`[::1]:8080`); the brackets are stripped from the returned
+/// host. A missing host, a missing port or a port outside `0..=65535` is rejected.
+pub(crate) fn resolve_endpoint(addr: &str) -> Result<(String, u16)> {
+    let (host, port) = split_host_port(addr)
+        .map_err(|reason| Error::invalid_config(format!("invalid address `{addr}`: {reason}")))?;
+    let host = host
+        .strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host);
+    Ok((host.to_string(), port))
+}
+
+/// Core of [`resolve_endpoint`]; the error is a short reason to be embedded by the caller.
+fn split_host_port(addr: &str) -> std::result::Result<(&str, u16), &'static str> {
+    let (host, port) = addr.rsplit_once(':').ok_or("expect host:port")?;
     if host.is_empty() {
-        return Err(Error::invalid_config(format!(
-            "invalid `{field}` = `{addr}`: empty host"
-        )));
+        return Err("empty host");
     }
-    port.parse::<u16>()
-        .map_err(|_| Error::invalid_config(format!("invalid `{field}` = `{addr}`: bad port")))?;
-    Ok(())
+    if is_unbracketed_ipv6(host) {
+        return Err("IPv6 host must be bracketed");
+    }
+    let port = port.parse::<u16>().map_err(|_| "bad port")?;
+    Ok((host, port))
+}
+
+/// Whether the host part of a `host:port` split still contains a colon outside of brackets,
+/// i.e. an IPv6 literal written without `[...]`.
+fn is_unbracketed_ipv6(host: &str) -> bool {
+    host.contains(':') && !(host.starts_with('[') && host.ends_with(']'))
 }
 
 #[cfg(test)]
@@ -276,57 +1029,828 @@ fn validate_host_port(addr: &str, field: &str) -> Result<()> {
 mod tests {
     use super::*;
 
+    fn lookup_from<'a>(
+        vars: &'a [(&'a str, &'a str)],
+    ) -> impl Fn(&str) -> std::result::Result<String, env::VarError> + 'a {
+        move |name| {
+            vars.iter()
+                .find(|(k, _)| *k == name)
+                .map(|(_, v)| v.to_string())
+                .ok_or(env::VarError::NotPresent)
+        }
+    }
+
+    const BASE_ENV: [(&str, &str); 5] = [
+        (env_keys::NACOS_ADDR, "127.0.0.1:8848"),
+        (env_keys::NACOS_NAMESPACE, "public"),
+        (env_keys::SERVICE_ADDR, "0.0.0.0:9000"),
+        (env_keys::SERVICE_NAME, "svc"),
+        (env_keys::SERVICE_HOST, "10.0.0.1"),
+    ];
+
     #[test]
-    fn builder_requires_mandatory_fields() {
-        let err = ServiceConfig::builder().build().unwrap_err();
-        assert!(matches!(err, Error::InvalidConfig(_)));
+    fn from_lookup_defaults_group() {
+        let cfg = ServiceConfig::from_lookup("", lookup_from(&BASE_ENV)).unwrap();
+        assert_eq!(cfg.group, DEFAULT_GROUP);
     }
 
     #[test]
-    fn builder_bind_addr_extracts_port() {
-        let cfg = ServiceConfig::builder()
+    fn from_lookup_applies_prefix() {
+        let vars: Vec<(String, &str)> = BASE_ENV
+            .iter()
+            .map(|(k, v)| (format!("MYAPP_{k}"), *v))
+            .collect();
+        let vars: Vec<(&str, &str)> = vars.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+        let cfg = ServiceConfig::from_lookup("MYAPP_", lookup_from(&vars)).unwrap();
+        assert_eq!(cfg.service_name, "svc");
+
+        let err = ServiceConfig::from_lookup("OTHER_", lookup_from(&vars)).unwrap_err();
+        assert!(matches!(err, Error::Env { ref name, .. } if name == "OTHER_NACOS_ADDR"));
+    }
+
+    #[test]
+    fn from_lookup_accepts_bare_port() {
+        let mut vars = BASE_ENV.to_vec();
+        vars[2] = (env_keys::SERVICE_ADDR, "9100");
+        let cfg = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap();
+        assert_eq!(cfg.service_port, 9100);
+
+        let mut vars = BASE_ENV.to_vec();
+        vars[2] = (env_keys::SERVICE_PORT, "9200");
+        let cfg = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap();
+        assert_eq!(cfg.service_port, 9200);
+
+        let mut vars = BASE_ENV.to_vec();
+        vars.remove(2);
+        let err = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap_err();
+        assert!(matches!(err, Error::Env { ref name, .. } if name == env_keys::SERVICE_ADDR));
+    }
+
+    #[test]
+    fn port_must_be_in_range() {
+        let base = ServiceConfig::builder()
             .nacos_addr("127.0.0.1:8848")
             .namespace("public")
             .service_name("svc")
-            .bind_addr("0.0.0.0:9000")
-            .unwrap()
+            .service_host("1.2.3.4");
+        let err = base.clone().service_port(0).build().unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(ref m) if m.contains("service_port")));
+        let err = base
+            .clone()
+            .service_port(9000)
+            .http_port(0)
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(ref m) if m.contains("http_port")));
+        assert!(base.clone().bind_addr("70000").is_err());
+        assert!(base.bind_addr("65535").unwrap().build().is_ok());
+    }
+
+    #[test]
+    fn missing_namespace_means_public() {
+        let vars: Vec<_> = BASE_ENV
+            .iter()
+            .filter(|(k, _)| *k != env_keys::NACOS_NAMESPACE)
+            .copied()
+            .collect();
+        let cfg = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap();
+        assert_eq!(cfg.namespace, "");
+
+        let cfg = ServiceConfig::builder()
+            .nacos_addr("127.0.0.1:8848")
+            .service_name("svc")
             .service_host("10.0.0.1")
+            .service_port(9000)
             .build()
             .unwrap();
-        assert_eq!(cfg.service_port, 9000);
-        assert_eq!(cfg.service_host, "10.0.0.1");
-        assert_eq!(
-            cfg.metadata.get(META_GRPC_PORT).map(String::as_str),
-            Some("9000")
-        );
+        assert_eq!(cfg.namespace, "");
     }
 
     #[test]
-    fn builder_rejects_bad_bind_addr() {
-        let err = ServiceConfig::builder()
-            .bind_addr("no-port-here")
-            .unwrap_err();
-        assert!(matches!(err, Error::InvalidConfig(_)));
+    fn from_lookup_reads_group() {
+        let mut vars = BASE_ENV.to_vec();
+        vars.push((env_keys::NACOS_GROUP, "DEV_GROUP"));
+        let cfg = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap();
+        assert_eq!(cfg.group, "DEV_GROUP");
+    }
+
+    #[test]
+    fn from_lookup_reads_weight() {
+        let mut vars = BASE_ENV.to_vec();
+        vars.push((env_keys::SERVICE_WEIGHT, "2.5"));
+        let cfg = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap();
+        assert_eq!(cfg.weight, 2.5);
 
-        let err = ServiceConfig::builder().bind_addr(":9000").unwrap_err();
+        let mut vars = BASE_ENV.to_vec();
+        vars.push((env_keys::SERVICE_WEIGHT, "heavy"));
+        let err = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap_err();
         assert!(matches!(err, Error::InvalidConfig(_)));
+    }
 
-        let err = ServiceConfig::builder()
-            .bind_addr("host:not-a-port")
-            .unwrap_err();
+    #[test]
+    fn from_lookup_reads_ephemeral() {
+        let cfg = ServiceConfig::from_lookup("", lookup_from(&BASE_ENV)).unwrap();
+        assert!(cfg.ephemeral);
+
+        let mut vars = BASE_ENV.to_vec();
+        vars.push((env_keys::SERVICE_EPHEMERAL, "false"));
+        let cfg = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap();
+        assert!(!cfg.ephemeral);
+
+        let mut vars = BASE_ENV.to_vec();
+        vars.push((env_keys::SERVICE_EPHEMERAL, "maybe"));
+        let err = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap_err();
         assert!(matches!(err, Error::InvalidConfig(_)));
     }
 
     #[test]
-    fn validate_host_port_accepts_hostname() {
-        assert!(validate_host_port("nacos.internal:8848", "nacos_addr").is_ok());
-        assert!(validate_host_port("localhost:8848", "nacos_addr").is_ok());
-        assert!(validate_host_port("127.0.0.1:8848", "nacos_addr").is_ok());
+    fn from_lookup_reads_cluster() {
+        let cfg = ServiceConfig::from_lookup("", lookup_from(&BASE_ENV)).unwrap();
+        assert_eq!(cfg.cluster_name, None);
+
+        let mut vars = BASE_ENV.to_vec();
+        vars.push((env_keys::SERVICE_CLUSTER, "zone-a"));
+        let cfg = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap();
+        assert_eq!(cfg.cluster_name.as_deref(), Some("zone-a"));
     }
 
     #[test]
-    fn validate_host_port_rejects_empty_host() {
-        assert!(validate_host_port(":8848", "nacos_addr").is_err());
+    fn from_lookup_reads_metadata_json() {
+        let mut vars = BASE_ENV.to_vec();
+        vars.push((env_keys::SERVICE_METADATA, r#"{"version":"2","zone":"a"}"#));
+        let cfg = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap();
+        assert_eq!(cfg.metadata.get("version").map(String::as_str), Some("2"));
+        assert_eq!(cfg.metadata.get("zone").map(String::as_str), Some("a"));
+        assert_eq!(
+            cfg.metadata.get(META_GRPC_PORT).map(String::as_str),
+            Some("9000")
+        );
+
+        for bad in [r#"{"version":2}"#, "[]", "version=2"] {
+            let mut vars = BASE_ENV.to_vec();
+            vars.push((env_keys::SERVICE_METADATA, bad));
+            let err = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap_err();
+            assert!(
+                matches!(err, Error::InvalidConfig(ref m) if m.contains("SERVICE_METADATA")),
+                "{bad}: {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn expand_vars_substitutes_references() {
+        let lookup = |name: &str| match name {
+            "BUILD_VERSION" => Some("1.4.2".to_string()),
+            "ZONE" => Some("a".to_string()),
+            _ => None,
+        };
+        assert_eq!(expand_vars("plain", lookup).unwrap(), "plain");
+        assert_eq!(
+            expand_vars("v${BUILD_VERSION}-${ZONE}", lookup).unwrap(),
+            "v1.4.2-a"
+        );
+        assert_eq!(expand_vars("$5 {x}", lookup).unwrap(), "$5 {x}");
+        assert!(
+            expand_vars("${MISSING}", lookup)
+                .unwrap_err()
+                .contains("MISSING")
+        );
+        assert!(expand_vars("${ZONE", lookup).is_err());
+        assert!(expand_vars("${}", lookup).is_err());
+    }
+
+    #[test]
+    fn from_lookup_expands_metadata_references_on_request() {
+        let mut vars = BASE_ENV.to_vec();
+        vars.push((
+            env_keys::SERVICE_METADATA,
+            r#"{"version":"${BUILD_VERSION}"}"#,
+        ));
+        let cfg = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap();
+        assert_eq!(
+            cfg.metadata.get("version").map(String::as_str),
+            Some("${BUILD_VERSION}")
+        );
+
+        vars.push((env_keys::SERVICE_METADATA_EXPAND, "true"));
+        let err = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(ref m) if m.contains("BUILD_VERSION")));
+
+        vars.push(("BUILD_VERSION", "1.4.2"));
+        let cfg = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap();
+        assert_eq!(
+            cfg.metadata.get("version").map(String::as_str),
+            Some("1.4.2")
+        );
+    }
+
+    #[test]
+    fn from_lookup_reads_auth_pair() {
+        let mut vars = BASE_ENV.to_vec();
+        vars.push((env_keys::NACOS_USERNAME, "nacos"));
+        vars.push((env_keys::NACOS_PASSWORD, "secret"));
+        let cfg = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap();
+        assert_eq!(cfg.auth, Some(("nacos".to_string(), "secret".to_string())));
+
+        let mut vars = BASE_ENV.to_vec();
+        vars.push((env_keys::NACOS_USERNAME, "nacos"));
+        let err = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(_)));
+    }
+
+    #[test]
+    fn from_lookup_reads_access_key_pair() {
+        let mut vars = BASE_ENV.to_vec();
+        vars.push((env_keys::NACOS_SK, "sk"));
+        let err = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(ref m) if m.contains("NACOS_AK")));
+
+        vars.push((env_keys::NACOS_AK, "ak"));
+        let result = ServiceConfig::from_lookup("", lookup_from(&vars));
+        if cfg!(feature = "aliyun-auth") {
+            let cfg = result.unwrap();
+            assert_eq!(cfg.access_key, Some(("ak".to_string(), "sk".to_string())));
+        } else {
+            let err = result.unwrap_err();
+            assert!(matches!(err, Error::InvalidConfig(ref m) if m.contains("aliyun-auth")));
+        }
+    }
+
+    #[cfg(feature = "aliyun-auth")]
+    #[test]
+    fn access_key_excludes_username_auth() {
+        let err = ServiceConfig::builder()
+            .nacos_addr("127.0.0.1:8848")
+            .namespace("public")
+            .service_name("svc")
+            .service_host("10.0.0.1")
+            .service_port(9000)
+            .auth("nacos", "secret")
+            .access_key("ak", "sk")
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(ref m) if m.contains("mutually exclusive")));
+    }
+
+    #[test]
+    fn from_lookup_reads_all_base_fields() {
+        let cfg = ServiceConfig::from_lookup("", lookup_from(&BASE_ENV)).unwrap();
+        assert_eq!(cfg.nacos_addr, "127.0.0.1:8848");
+        assert_eq!(cfg.namespace, "public");
+        assert_eq!(cfg.service_name, "svc");
+        assert_eq!(cfg.service_host, "10.0.0.1");
+        assert_eq!(cfg.service_port, 9000);
+    }
+
+    #[test]
+    fn endpoint_makes_nacos_addr_optional() {
+        let builder = || {
+            ServiceConfig::builder()
+                .service_name("svc")
+                .service_host("10.0.0.1")
+                .service_port(9000)
+        };
+        let cfg = builder()
+            .endpoint("http://addr:8080/nacos/serverlist")
+            .build()
+            .unwrap();
+        assert_eq!(cfg.nacos_addr, "");
+        assert!(builder().endpoint(" ").build().is_err());
+        assert!(builder().build().is_err());
+
+        let vars: Vec<_> = BASE_ENV
+            .iter()
+            .filter(|(k, _)| *k != env_keys::NACOS_ADDR)
+            .copied()
+            .chain([(env_keys::NACOS_ENDPOINT, "addr:9090")])
+            .collect();
+        let cfg = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap();
+        assert_eq!(cfg.endpoint.as_deref(), Some("addr:9090"));
+    }
+
+    #[test]
+    fn from_lookup_reports_each_missing_required_key() {
+        for missing in [env_keys::NACOS_ADDR, env_keys::SERVICE_NAME] {
+            let vars: Vec<_> = BASE_ENV
+                .iter()
+                .filter(|(k, _)| *k != missing)
+                .copied()
+                .collect();
+            let err = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap_err();
+            assert!(
+                matches!(err, Error::Env { ref name, source: env::VarError::NotPresent } if name == missing),
+                "unexpected error for missing {missing}: {err:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn from_lookup_rejects_non_numeric_port() {
+        let mut vars = BASE_ENV.to_vec();
+        vars[2] = (env_keys::SERVICE_ADDR, "127.0.0.1:notaport");
+        let err = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap_err();
+        assert!(matches!(err, Error::InvalidServiceAddr(ref addr) if addr == "127.0.0.1:notaport"));
+    }
+
+    #[test]
+    fn builder_rejects_zero_timeout() {
+        let builder = ServiceConfig::builder()
+            .nacos_addr("127.0.0.1:8848")
+            .namespace("public")
+            .service_name("svc")
+            .service_host("10.0.0.1")
+            .service_port(9000);
+        let cfg = builder.clone().build().unwrap();
+        assert_eq!(cfg.timeout, None);
+        let cfg = builder
+            .clone()
+            .timeout(Duration::from_secs(3))
+            .build()
+            .unwrap();
+        assert_eq!(cfg.timeout, Some(Duration::from_secs(3)));
+        let err = builder.timeout(Duration::ZERO).build().unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(ref m) if m.contains("timeout")));
+    }
+
+    #[test]
+    fn builder_rejects_zero_keepalive() {
+        let builder = ServiceConfig::builder()
+            .nacos_addr("127.0.0.1:8848")
+            .namespace("public")
+            .service_name("svc")
+            .service_host("10.0.0.1")
+            .service_port(9000);
+        let cfg = builder
+            .clone()
+            .keepalive(Duration::from_secs(30))
+            .build()
+            .unwrap();
+        assert_eq!(cfg.keepalive, Some(Duration::from_secs(30)));
+        let err = builder.keepalive(Duration::ZERO).build().unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(ref m) if m.contains("keepalive")));
+    }
+
+    #[test]
+    fn builder_rejects_negative_weight() {
+        let err = ServiceConfig::builder()
+            .nacos_addr("127.0.0.1:8848")
+            .namespace("public")
+            .service_name("svc")
+            .service_host("1.2.3.4")
+            .service_port(9000)
+            .weight(-1.0)
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(_)));
+    }
+
+    #[test]
+    fn builder_requires_mandatory_fields() {
+        let err = ServiceConfig::builder().build().unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(_)));
+    }
+
+    #[test]
+    fn builder_rejects_empty_fields() {
+        let base = ServiceConfig::builder()
+            .nacos_addr("127.0.0.1:8848")
+            .namespace("public")
+            .service_name("svc")
+            .service_host("1.2.3.4")
+            .service_port(9000);
+
+        let err = base.clone().service_name("  ").build().unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(ref m) if m.contains("service_name")));
+
+        let cfg = base.clone().namespace("").build().unwrap();
+        assert_eq!(cfg.namespace, "");
+
+        let err = base.nacos_addr("").build().unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(ref m) if m.contains("nacos_addr")));
+
+        let err = ServiceConfig::builder().bind_addr("").unwrap_err();
+        assert!(matches!(err, Error::InvalidServiceAddr(_)));
+    }
+
+    #[test]
+    fn validate_catches_hand_edited_fields() {
+        let mut cfg = ServiceConfig::builder()
+            .nacos_addr("127.0.0.1:8848")
+            .namespace("public")
+            .service_name("svc")
+            .service_host("1.2.3.4")
+            .service_port(9000)
+            .build()
+            .unwrap();
+        assert!(cfg.validate().is_ok());
+
+        cfg.group = String::new();
+        let err = cfg.validate().unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(ref m) if m.contains("group")));
+    }
+
+    #[test]
+    fn builder_bind_addr_extracts_port() {
+        let cfg = ServiceConfig::builder()
+            .nacos_addr("127.0.0.1:8848")
+            .namespace("public")
+            .service_name("svc")
+            .bind_addr("0.0.0.0:9000")
+            .unwrap()
+            .service_host("10.0.0.1")
+            .build()
+            .unwrap();
+        assert_eq!(cfg.service_port, 9000);
+        assert_eq!(cfg.service_host, "10.0.0.1");
+        assert_eq!(
+            cfg.metadata.get(META_GRPC_PORT).map(String::as_str),
+            Some("9000")
+        );
+    }
+
+    #[test]
+    fn builder_rejects_bad_bind_addr() {
+        for bad in ["no-port-here", ":9000", "host:not-a-port", "host:70000"] {
+            let err = ServiceConfig::builder().bind_addr(bad).unwrap_err();
+            assert!(
+                matches!(err, Error::InvalidServiceAddr(ref addr) if addr == bad),
+                "{bad}: {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn builder_bind_addr_accepts_ipv6() {
+        let cfg = ServiceConfig::builder()
+            .nacos_addr("[::1]:8848")
+            .namespace("public")
+            .service_name("svc")
+            .bind_addr("[::1]:8080")
+            .unwrap()
+            .service_host("::1")
+            .build()
+            .unwrap();
+        assert_eq!(cfg.service_port, 8080);
+        assert_eq!(
+            cfg.metadata.get(META_GRPC_PORT).map(String::as_str),
+            Some("8080")
+        );
+    }
+
+    #[test]
+    fn builder_rejects_unbracketed_ipv6() {
+        let err = ServiceConfig::builder().bind_addr("::1:8080").unwrap_err();
+        assert!(matches!(err, Error::InvalidServiceAddr(_)));
+        assert!(validate_host_port("::1:8848", "nacos_addr").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn builder_deserializes_from_toml() {
+        let builder: ServiceConfigBuilder = toml::from_str(
+            r#"
+                nacos_addr = "127.0.0.1:8848"
+                namespace = "public"
+                service_name = "svc"
+                service_port = 9000
+
+                [metadata]
+                version = "1.0"
+            "#,
+        )
+        .unwrap();
+        let cfg = builder.service_host("10.0.0.1").build().unwrap();
+        assert_eq!(cfg.service_name, "svc");
+        assert_eq!(cfg.service_port, 9000);
+        assert_eq!(cfg.group, DEFAULT_GROUP);
+        assert_eq!(cfg.metadata["version"], "1.0");
+        assert_eq!(cfg.metadata[META_GRPC_PORT], "9000");
+    }
+
+    #[test]
+    fn resolve_endpoint_splits_ipv4_and_hostname() {
+        assert_eq!(
+            resolve_endpoint("10.0.0.1:9000").unwrap(),
+            ("10.0.0.1".to_string(), 9000)
+        );
+        assert_eq!(
+            resolve_endpoint("nacos.internal:8848").unwrap(),
+            ("nacos.internal".to_string(), 8848)
+        );
+    }
+
+    #[test]
+    fn resolve_endpoint_unbrackets_ipv6() {
+        assert_eq!(
+            resolve_endpoint("[::1]:8080").unwrap(),
+            ("::1".to_string(), 8080)
+        );
+        assert_eq!(
+            resolve_endpoint("[fe80::1%eth0]:80").unwrap(),
+            ("fe80::1%eth0".to_string(), 80)
+        );
+    }
+
+    #[test]
+    fn resolve_endpoint_rejects_malformed_input() {
+        for addr in [
+            "10.0.0.1",
+            "10.0.0.1:",
+            ":9000",
+            "host:70000",
+            "host:-1",
+            "host:abc",
+            "::1:8080",
+        ] {
+            let err = resolve_endpoint(addr).unwrap_err();
+            assert!(
+                matches!(err, Error::InvalidConfig(ref m) if m.contains(addr)),
+                "unexpected error for {addr:?}: {err:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn validate_host_port_accepts_hostname() {
+        assert!(validate_host_port("nacos.internal:8848", "nacos_addr").is_ok());
+        assert!(validate_host_port("localhost:8848", "nacos_addr").is_ok());
+        assert!(validate_host_port("127.0.0.1:8848", "nacos_addr").is_ok());
+    }
+
+    #[test]
+    fn validate_accepts_nacos_cluster_list() {
+        let cfg = ServiceConfig::builder()
+            .nacos_addr("10.0.0.1:8848, 10.0.0.2:8848,nacos-3.internal:8848")
+            .namespace("public")
+            .service_name("svc")
+            .service_host("10.0.0.1")
+            .service_port(9000)
+            .build()
+            .unwrap();
+        assert_eq!(
+            cfg.nacos_server_list(),
+            "10.0.0.1:8848,10.0.0.2:8848,nacos-3.internal:8848"
+        );
+
+        for bad in ["10.0.0.1:8848,", "10.0.0.1:8848,,10.0.0.2:8848", "a:8848,b"] {
+            let mut cfg = cfg.clone();
+            cfg.nacos_addr = bad.to_string();
+            let err = cfg.validate().unwrap_err();
+            assert!(
+                matches!(err, Error::InvalidConfig(ref m) if m.contains("nacos_addr")),
+                "unexpected result for {bad:?}: {err:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn app_name_defaults_to_service_name() {
+        let builder = ServiceConfig::builder()
+            .nacos_addr("127.0.0.1:8848")
+            .namespace("public")
+            .service_name("svc")
+            .service_host("10.0.0.1")
+            .service_port(9000);
+        let cfg = builder.clone().build().unwrap();
+        assert_eq!(cfg.app_name, None);
+        assert_eq!(cfg.client_app_name(), Some("svc"));
+
+        let cfg = builder.app_name("svc-console").build().unwrap();
+        assert_eq!(cfg.client_app_name(), Some("svc-console"));
+    }
+
+    #[test]
+    fn discovery_only_skips_instance_fields() {
+        let builder = ServiceConfig::builder()
+            .nacos_addr("127.0.0.1:8848")
+            .namespace("public");
+        let err = builder.clone().build().unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(ref m) if m.contains("service_name")));
+
+        let cfg = builder.clone().discovery_only().build().unwrap();
+        assert!(cfg.discovery_only);
+        assert_eq!(cfg.service_name, "");
+        assert_eq!(cfg.service_port, 0);
+        assert!(cfg.metadata.is_empty());
+        assert_eq!(cfg.client_app_name(), None);
+
+        let err = builder.discovery_only().group(" ").build().unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(ref m) if m.contains("group")));
+    }
+
+    #[test]
+    fn validate_host_port_rejects_empty_host() {
+        assert!(validate_host_port(":8848", "nacos_addr").is_err());
+    }
+
+    #[test]
+    fn metadata_custom_keys_merge_with_default() {
+        let cfg = ServiceConfig::builder()
+            .nacos_addr("127.0.0.1:8848")
+            .namespace("public")
+            .service_name("svc")
+            .service_host("1.2.3.4")
+            .service_port(9000)
+            .metadata("version", "1.2.0")
+            .metadata_all([("zone", "cn-east-1a"), ("protocol", "grpc")])
+            .build()
+            .unwrap();
+        assert_eq!(cfg.metadata.len(), 4);
+        assert_eq!(cfg.metadata[META_GRPC_PORT], "9000");
+        assert_eq!(cfg.metadata["version"], "1.2.0");
+        assert_eq!(cfg.metadata["zone"], "cn-east-1a");
+        assert_eq!(cfg.metadata["protocol"], "grpc");
+    }
+
+    #[test]
+    fn metadata_grpc_port_key_can_be_overridden() {
+        let base = ServiceConfig::builder()
+            .nacos_addr("127.0.0.1:8848")
+            .namespace("public")
+            .service_name("svc")
+            .service_host("1.2.3.4")
+            .service_port(9000);
+
+        let cfg = base.clone().grpc_port_key("grpc.port").build().unwrap();
+        assert_eq!(cfg.metadata["grpc.port"], "9000");
+        assert!(!cfg.metadata.contains_key(META_GRPC_PORT));
+
+        let err = base.grpc_port_key("").build().unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(ref m) if m.contains("grpc_port_key")));
+    }
+
+    #[test]
+    fn metadata_includes_http_port_when_set() {
+        let cfg = ServiceConfig::builder()
+            .nacos_addr("127.0.0.1:8848")
+            .namespace("public")
+            .service_name("svc")
+            .service_host("1.2.3.4")
+            .service_port(9000)
+            .http_port(8080)
+            .build()
+            .unwrap();
+        assert_eq!(cfg.metadata[META_GRPC_PORT], "9000");
+        assert_eq!(cfg.metadata[META_HTTP_PORT], "8080");
+    }
+
+    #[test]
+    fn instance_id_must_not_be_blank() {
+        let builder = ServiceConfig::builder()
+            .nacos_addr("127.0.0.1:8848")
+            .namespace("public")
+            .service_name("svc")
+            .service_host("10.0.0.1")
+            .service_port(9000);
+        assert_eq!(builder.clone().build().unwrap().instance_id, None);
+        let cfg = builder.clone().instance_id("svc-0").build().unwrap();
+        assert_eq!(cfg.instance_id.as_deref(), Some("svc-0"));
+        let err = builder.instance_id(" ").build().unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(ref m) if m.contains("instance_id")));
+    }
+
+    #[test]
+    fn zone_sets_cluster_and_metadata() {
+        let cfg = ServiceConfig::builder()
+            .nacos_addr("127.0.0.1:8848")
+            .namespace("public")
+            .service_name("svc")
+            .service_host("10.0.0.1")
+            .service_port(9000)
+            .zone("cn-east-1a")
+            .build()
+            .unwrap();
+        assert_eq!(cfg.cluster_name.as_deref(), Some("cn-east-1a"));
+        assert_eq!(
+            cfg.metadata.get(META_ZONE).map(String::as_str),
+            Some("cn-east-1a")
+        );
+    }
+
+    #[test]
+    fn explicit_host_skips_detection() {
+        // An interface that cannot exist, so resolving the strategy always fails.
+        let builder = || {
+            ServiceConfig::builder()
+                .nacos_addr("127.0.0.1:8848")
+                .service_name("svc")
+                .service_port(9000)
+                .host_strategy(HostStrategy::Interface("no-such-if0".to_string()))
+        };
+        assert!(builder().build().is_err());
+        let cfg = builder().service_host("10.0.0.1").build().unwrap();
+        assert_eq!(cfg.service_host, "10.0.0.1");
+    }
+
+    #[test]
+    fn host_resolver_runs_only_without_explicit_host() {
+        let builder = || {
+            ServiceConfig::builder()
+                .nacos_addr("127.0.0.1:8848")
+                .service_name("svc")
+                .service_port(9000)
+                .host_strategy(HostStrategy::Interface("no-such-if0".to_string()))
+        };
+        let cfg = builder()
+            .host_resolver(|| Ok("10.9.8.7".to_string()))
+            .build()
+            .unwrap();
+        assert_eq!(cfg.service_host, "10.9.8.7");
+
+        let failing = || Err(Error::invalid_config("metadata service unreachable"));
+        let err = builder().host_resolver(failing).build().unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(ref m) if m.contains("unreachable")));
+        let cfg = builder()
+            .host_resolver(failing)
+            .service_host("10.0.0.1")
+            .build()
+            .unwrap();
+        assert_eq!(cfg.service_host, "10.0.0.1");
+    }
+
+    #[test]
+    fn from_lookup_resolves_service_iface() {
+        let mut vars = BASE_ENV.to_vec();
+        vars.retain(|(k, _)| *k != env_keys::SERVICE_HOST);
+        vars.push((env_keys::SERVICE_IFACE, "no-such-if0"));
+        let err = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(ref m) if m.contains("`no-such-if0`")));
+
+        // An explicit host still wins over the interface.
+        vars.push((env_keys::SERVICE_HOST, "10.0.0.1"));
+        let cfg = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap();
+        assert_eq!(cfg.service_host, "10.0.0.1");
+    }
+
+    #[test]
+    fn from_lookup_rejects_invalid_prefer_ipv6() {
+        let mut vars = BASE_ENV.to_vec();
+        vars.push((env_keys::SERVICE_PREFER_IPV6, "yes"));
+        let err = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(ref m) if m.contains("SERVICE_PREFER_IPV6")));
+
+        vars.pop();
+        vars.push((env_keys::SERVICE_PREFER_IPV6, "true"));
+        assert!(ServiceConfig::from_lookup("", lookup_from(&vars)).is_ok());
+    }
+
+    #[test]
+    fn from_lookup_prefers_service_host() {
+        let mut vars = BASE_ENV.to_vec();
+        vars.retain(|(k, _)| *k != env_keys::SERVICE_HOST);
+        vars.push((env_keys::SERVICE_HOST, "svc.internal"));
+        let cfg = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap();
+        assert_eq!(cfg.service_host, "svc.internal");
+    }
+
+    #[test]
+    fn extra_groups_must_be_distinct() {
+        let builder = || {
+            ServiceConfig::builder()
+                .nacos_addr("127.0.0.1:8848")
+                .service_name("svc")
+                .service_host("10.0.0.1")
+                .service_port(9000)
+                .group("A")
+        };
+        let cfg = builder().extra_groups(["B", "C"]).build().unwrap();
+        assert_eq!(cfg.extra_groups, ["B", "C"]);
+
+        assert!(builder().extra_groups(["A"]).build().is_err());
+        assert!(builder().extra_groups(["B", "B"]).build().is_err());
+        assert!(builder().extra_groups([" "]).build().is_err());
+    }
+
+    #[test]
+    fn protocol_is_validated() {
+        let builder = || {
+            ServiceConfig::builder()
+                .nacos_addr("127.0.0.1:8848")
+                .service_name("svc")
+                .service_host("10.0.0.1")
+                .service_port(9000)
+        };
+        let cfg = builder().protocol("grpcs").build().unwrap();
+        assert_eq!(
+            cfg.metadata.get(META_PROTOCOL).map(String::as_str),
+            Some("grpcs")
+        );
+
+        let err = builder().protocol("tcp").build().unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(ref m) if m.contains("tcp")));
+        // Also enforced when set as plain metadata.
+        assert!(builder().metadata(META_PROTOCOL, "GRPC").build().is_err());
+    }
+
+    #[test]
+    fn from_lookup_reads_protocol() {
+        let mut vars = BASE_ENV.to_vec();
+        vars.push((env_keys::SERVICE_PROTOCOL, "http"));
+        let cfg = ServiceConfig::from_lookup("", lookup_from(&vars)).unwrap();
+        assert_eq!(
+            cfg.metadata.get(META_PROTOCOL).map(String::as_str),
+            Some("http")
+        );
     }
 
     #[test]
@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use nacos_sdk::api::naming::ServiceInstance;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::registry::Registry;
+
+/// [`Registry`] backed by the Consul agent HTTP API (`consul` feature).
+///
+/// Nacos concepts are mapped as follows: the group becomes a service tag (queries filter on
+/// it), metadata becomes Consul `Meta`, the weight becomes the `Passing` weight, and an
+/// instance counts as healthy when all of its checks are passing.
+///
+/// Consul keys services by ID alone, so the group is part of the ID (`<id>-<group>`, where
+/// `<id>` is the instance id or `service-ip-port`). This lets one instance be registered in
+/// several groups, e.g. via [`extra_groups`](crate::ServiceConfigBuilder::extra_groups);
+/// listed instances report the ID without the group suffix.
+///
+/// Consul weights are positive integers, so the conversion is lossy: weights are rounded to
+/// the nearest integer, with anything below `1.5` becoming `1`. A weight of `0` cannot be
+/// expressed and is rejected with [`Error::Unsupported`]; drain an instance by
+/// deregistering it instead.
+#[derive(Debug, Clone)]
+pub struct ConsulRegistry {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl ConsulRegistry {
+    /// Talk to the Consul agent at `base_url`, e.g. `http://127.0.0.1:8500`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl Registry for ConsulRegistry {
+    async fn register(
+        &self,
+        service_name: &str,
+        group: &str,
+        instance: ServiceInstance,
+    ) -> Result<()> {
+        let body = Registration::new(service_name, group, &instance)?;
+        self.client
+            .put(format!("{}/v1/agent/service/register", self.base_url))
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    async fn deregister(
+        &self,
+        service_name: &str,
+        group: &str,
+        instance: ServiceInstance,
+    ) -> Result<()> {
+        let id = encode_path_segment(&service_id(service_name, group, &instance));
+        self.client
+            .put(format!(
+                "{}/v1/agent/service/deregister/{id}",
+                self.base_url
+            ))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    async fn get_instances(&self, service_name: &str, group: &str) -> Result<Vec<ServiceInstance>> {
+        let entries: Vec<HealthEntry> = self
+            .client
+            .get(format!(
+                "{}/v1/health/service/{}",
+                self.base_url,
+                encode_path_segment(service_name)
+            ))
+            .query(&[("tag", group)])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(entries
+            .into_iter()
+            .map(|entry| entry.into_instance(service_name, group))
+            .collect())
+    }
+}
+
+/// Consul service id: the explicit instance id, or `service-ip-port`, suffixed with the group.
+fn service_id(service_name: &str, group: &str, instance: &ServiceInstance) -> String {
+    match &instance.instance_id {
+        Some(id) => format!("{id}-{group}"),
+        None => format!("{service_name}-{}-{}-{group}", instance.ip, instance.port),
+    }
+}
+
+/// Percent-encode `segment` for use as one URL path segment, keeping only RFC 3986 unreserved
+/// characters as-is, so names containing `/`, `?` or `#` cannot address another endpoint.
+fn encode_path_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(char::from(byte));
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+/// Body of `PUT /v1/agent/service/register`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Registration {
+    #[serde(rename = "ID")]
+    id: String,
+    name: String,
+    tags: Vec<String>,
+    address: String,
+    port: i32,
+    meta: HashMap<String, String>,
+    weights: Weights,
+}
+
+impl Registration {
+    fn new(service_name: &str, group: &str, instance: &ServiceInstance) -> Result<Self> {
+        if instance.weight.is_nan() || instance.weight <= 0.0 {
+            return Err(Error::Unsupported(format!(
+                "consul cannot register `{service_name}` with weight {}: weights must be at least 1",
+                instance.weight
+            )));
+        }
+        Ok(Self {
+            id: service_id(service_name, group, instance),
+            name: service_name.to_string(),
+            tags: vec![group.to_string()],
+            address: instance.ip.clone(),
+            port: instance.port,
+            meta: instance.metadata.clone(),
+            weights: Weights {
+                passing: instance.weight.round().max(1.0) as u32,
+                warning: 1,
+            },
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Weights {
+    passing: u32,
+    warning: u32,
+}
+
+/// One element of `GET /v1/health/service/:name`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct HealthEntry {
+    service: AgentService,
+    #[serde(default)]
+    checks: Vec<HealthCheck>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AgentService {
+    #[serde(rename = "ID")]
+    id: String,
+    address: String,
+    port: i32,
+    #[serde(default)]
+    meta: HashMap<String, String>,
+    weights: Option<Weights>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct HealthCheck {
+    status: String,
+}
+
+impl HealthEntry {
+    fn into_instance(self, service_name: &str, group: &str) -> ServiceInstance {
+        let healthy = self.checks.iter().all(|c| c.status == "passing");
+        // Undo the group suffix of `service_id`; services registered elsewhere keep their ID.
+        let mut id = self.service.id;
+        if let Some(stripped) = id.strip_suffix(&format!("-{group}")) {
+            id.truncate(stripped.len());
+        }
+        ServiceInstance {
+            instance_id: Some(id),
+            ip: self.service.address,
+            port: self.service.port,
+            weight: self.service.weights.map_or(1.0, |w| f64::from(w.passing)),
+            healthy,
+            enabled: true,
+            service_name: Some(service_name.to_string()),
+            metadata: self.service.meta,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registration_maps_instance_fields() {
+        let instance = ServiceInstance {
+            ip: "10.0.0.1".to_string(),
+            port: 9000,
+            weight: 2.6,
+            metadata: HashMap::from([("gRPC_port".to_string(), "9000".to_string())]),
+            ..Default::default()
+        };
+        let body =
+            serde_json::to_value(Registration::new("svc", "DEV", &instance).unwrap()).unwrap();
+        assert_eq!(body["ID"], "svc-10.0.0.1-9000-DEV");
+        assert_eq!(body["Name"], "svc");
+        assert_eq!(body["Tags"][0], "DEV");
+        assert_eq!(body["Port"], 9000);
+        assert_eq!(body["Meta"]["gRPC_port"], "9000");
+        assert_eq!(body["Weights"]["Passing"], 3);
+    }
+
+    /// Minimal in-process stand-in for the Consul agent endpoints the registry calls. Returns
+    /// the base URL; registered services are keyed by ID, like the real agent.
+    async fn fake_agent() -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut services: HashMap<String, serde_json::Value> = HashMap::new();
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 4096];
+                let head_end = loop {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    if let Some(pos) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break pos + 4;
+                    }
+                };
+                let head = String::from_utf8_lossy(&request[..head_end]).to_string();
+                let length = head
+                    .lines()
+                    .find_map(|l| {
+                        l.to_ascii_lowercase()
+                            .strip_prefix("content-length:")
+                            .map(|v| v.trim().parse::<usize>().unwrap())
+                    })
+                    .unwrap_or(0);
+                while request.len() < head_end + length {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+                let body = &request[head_end..head_end + length];
+                let mut parts = head.split_whitespace();
+                let (method, target) = (parts.next().unwrap(), parts.next().unwrap());
+
+                let (status, reply) = if target == "/v1/agent/service/register" {
+                    let service: serde_json::Value = serde_json::from_slice(body).unwrap();
+                    services.insert(service["ID"].as_str().unwrap().to_string(), service);
+                    ("200 OK", String::new())
+                } else if let Some(id) = target.strip_prefix("/v1/agent/service/deregister/") {
+                    match services.remove(id) {
+                        Some(_) => ("200 OK", String::new()),
+                        None => ("404 Not Found", format!("Unknown service ID {id:?}")),
+                    }
+                } else if let Some(query) = target.strip_prefix("/v1/health/service/") {
+                    assert_eq!(method, "GET");
+                    let (name, tag) = query.split_once("?tag=").unwrap();
+                    let entries: Vec<_> = services
+                        .values()
+                        .filter(|s| s["Name"] == name && s["Tags"][0] == tag)
+                        .map(|s| serde_json::json!({ "Service": s, "Checks": [] }))
+                        .collect();
+                    ("200 OK", serde_json::to_string(&entries).unwrap())
+                } else {
+                    ("404 Not Found", String::new())
+                };
+                let response = format!(
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{reply}",
+                    reply.len()
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        base_url
+    }
+
+    #[tokio::test]
+    async fn one_instance_can_span_several_groups() {
+        let registry = ConsulRegistry::new(fake_agent().await);
+        let instance = ServiceInstance {
+            ip: "10.0.0.1".to_string(),
+            port: 9000,
+            ..Default::default()
+        };
+        for group in ["DEV", "SHARED"] {
+            registry
+                .register("svc", group, instance.clone())
+                .await
+                .unwrap();
+        }
+        for group in ["DEV", "SHARED"] {
+            let listed = registry.get_instances("svc", group).await.unwrap();
+            assert_eq!(listed.len(), 1, "{group}");
+            assert_eq!(listed[0].instance_id.as_deref(), Some("svc-10.0.0.1-9000"));
+        }
+
+        for group in ["DEV", "SHARED"] {
+            registry
+                .deregister("svc", group, instance.clone())
+                .await
+                .unwrap();
+            assert!(
+                registry
+                    .get_instances("svc", group)
+                    .await
+                    .unwrap()
+                    .is_empty()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn deregistering_an_unknown_instance_is_not_found() {
+        let base_url = fake_agent().await;
+        let registry = ConsulRegistry::new(&base_url);
+        let err = registry
+            .deregister("svc", "DEV", ServiceInstance::default())
+            .await
+            .unwrap_err();
+        assert!(err.is_not_found(), "{err}");
+
+        // `with_registry` ignores the Nacos address, but the builder still requires one.
+        let config = crate::ServiceConfig::builder()
+            .nacos_addr("127.0.0.1:8848")
+            .service_name("svc")
+            .service_host("10.0.0.1")
+            .service_port(9000)
+            .build()
+            .unwrap();
+        let manager =
+            crate::ServiceManager::with_registry(config, ConsulRegistry::new(base_url)).unwrap();
+        manager.register().await.unwrap();
+        manager.deregister().await.unwrap();
+        manager.deregister().await.unwrap();
+    }
+
+    #[test]
+    fn registration_rejects_zero_weight() {
+        let mut instance = ServiceInstance {
+            weight: 0.0,
+            ..Default::default()
+        };
+        let err = Registration::new("svc", "DEV", &instance).unwrap_err();
+        assert!(matches!(err, Error::Unsupported(ref m) if m.contains("weight 0")));
+
+        instance.weight = 0.2;
+        let body =
+            serde_json::to_value(Registration::new("svc", "DEV", &instance).unwrap()).unwrap();
+        assert_eq!(body["Weights"]["Passing"], 1);
+    }
+
+    #[test]
+    fn path_segments_are_percent_encoded() {
+        assert_eq!(encode_path_segment("orders-v1.2_x~"), "orders-v1.2_x~");
+        assert_eq!(encode_path_segment("a/b?c#d e"), "a%2Fb%3Fc%23d%20e");
+        assert_eq!(encode_path_segment("订单"), "%E8%AE%A2%E5%8D%95");
+    }
+
+    #[test]
+    fn health_entry_maps_to_instance() {
+        let entries: Vec<HealthEntry> = serde_json::from_str(
+            r#"[{
+                "Service": {"ID": "svc-1-DEV", "Address": "10.0.0.2", "Port": 9001,
+                            "Meta": {"zone": "a"}, "Weights": {"Passing": 5, "Warning": 1}},
+                "Checks": [{"Status": "passing"}, {"Status": "critical"}]
+            }]"#,
+        )
+        .unwrap();
+        let instance = entries
+            .into_iter()
+            .next()
+            .unwrap()
+            .into_instance("svc", "DEV");
+        assert_eq!(instance.instance_id.as_deref(), Some("svc-1"));
+        assert_eq!(instance.ip, "10.0.0.2");
+        assert_eq!(instance.port, 9001);
+        assert_eq!(instance.weight, 5.0);
+        assert!(!instance.healthy);
+        assert_eq!(instance.metadata["zone"], "a");
+    }
+}
@@ -1,6 +1,7 @@
 use std::io;
 use std::net::AddrParseError;
 use std::num::ParseIntError;
+use std::time::Duration;
 
 /// Crate-wide `Result` alias.
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -39,14 +40,176 @@ pub enum Error {
     #[error("nacos error: {0}")]
     Nacos(#[from] nacos_sdk::api::error::Error),
 
+    /// HTTP error talking to the Consul agent.
+    #[cfg(feature = "consul")]
+    #[error("consul error: {0}")]
+    Consul(#[from] reqwest::Error),
+
     /// Invalid configuration (missing required fields, malformed address, etc.).
     #[error("invalid configuration: {0}")]
     InvalidConfig(String),
+
+    /// A service address such as [`SERVICE_ADDR`](crate::env_keys::SERVICE_ADDR) is neither
+    /// `host:port` nor a bare port; carries the offending value.
+    #[error("invalid service address `{0}`: expect host:port or a bare port")]
+    InvalidServiceAddr(String),
+
+    /// The operation is not supported by the configured registry backend.
+    #[error("unsupported operation: {0}")]
+    Unsupported(String),
+
+    /// No healthy instance of the named service is available for selection.
+    #[error("no available instance for service `{0}`")]
+    NoAvailableInstance(String),
+
+    /// A registry call did not complete within the configured
+    /// [`timeout`](crate::ServiceConfigBuilder::timeout).
+    #[error("{operation} timed out after {timeout:?}")]
+    Timeout {
+        /// The operation that timed out, e.g. `"register"`.
+        operation: &'static str,
+        /// The limit that was exceeded.
+        timeout: Duration,
+    },
 }
 
 impl Error {
+    /// Build an [`Error::Env`] for the variable `name`.
+    ///
+    /// There is deliberately no `From<VarError>`: a bare `VarError` does not carry the
+    /// variable name, which is the most useful part of the message.
+    pub(crate) fn env(name: impl Into<String>, source: std::env::VarError) -> Self {
+        Self::Env {
+            name: name.into(),
+            source,
+        }
+    }
+
+    /// Whether the error is likely transient (network trouble, Nacos temporarily unreachable)
+    /// and the operation is worth retrying. Configuration and parse errors are not.
+    pub fn is_transient(&self) -> bool {
+        use nacos_sdk::api::error::Error as Nacos;
+
+        match self {
+            Self::Io(_) | Self::Timeout { .. } => true,
+            #[cfg(feature = "consul")]
+            Self::Consul(err) => err.is_connect() || err.is_timeout(),
+            Self::Nacos(err) => matches!(
+                err,
+                Nacos::NoAvailableServer
+                    | Nacos::ClientUnhealthy(_)
+                    | Nacos::ClientShutdown(_)
+                    | Nacos::TonicGrpcTransport(_)
+                    | Nacos::TonicGrpcStatus(_)
+                    | Nacos::GrpcBufferRequest(_)
+            ),
+            _ => false,
+        }
+    }
+
+    /// Whether the registry reported that the queried service or instance does not exist.
+    pub(crate) fn is_not_found(&self) -> bool {
+        /// Nacos `RESOURCE_NOT_FOUND` and `SERVICE_NOT_EXIST` error codes.
+        const NACOS_NOT_FOUND: [i32; 2] = [20004, 21008];
+
+        match self {
+            Self::Nacos(nacos_sdk::api::error::Error::ErrResponse(_, ret, code, _)) => {
+                *ret == 404 || NACOS_NOT_FOUND.contains(code)
+            }
+            #[cfg(feature = "consul")]
+            Self::Consul(err) => err.status() == Some(reqwest::StatusCode::NOT_FOUND),
+            _ => false,
+        }
+    }
+
     /// Build an [`Error::InvalidConfig`].
     pub(crate) fn invalid_config(msg: impl Into<String>) -> Self {
         Self::InvalidConfig(msg.into())
     }
 }
+
+/// Lets crates built around `io::Result` use `?` on this crate's results.
+///
+/// [`Error::Io`] is unwrapped as-is; every other variant is wrapped in an `io::Error` of the
+/// closest [`io::ErrorKind`], keeping this error as the source so its message is preserved.
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        let kind = match err {
+            Error::Io(inner) => return inner,
+            Error::Env { .. }
+            | Error::AddrParse(_)
+            | Error::PortParse(_)
+            | Error::InvalidConfig(_)
+            | Error::InvalidServiceAddr(_) => io::ErrorKind::InvalidInput,
+            Error::Unsupported(_) => io::ErrorKind::Unsupported,
+            Error::NoAvailableInstance(_) => io::ErrorKind::NotFound,
+            Error::Timeout { .. } => io::ErrorKind::TimedOut,
+            _ => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transient_classification() {
+        assert!(Error::Io(io::Error::other("reset")).is_transient());
+        assert!(Error::Nacos(nacos_sdk::api::error::Error::NoAvailableServer).is_transient());
+        assert!(
+            !Error::Nacos(nacos_sdk::api::error::Error::InvalidParam(
+                "service_name".to_string(),
+                "blank".to_string()
+            ))
+            .is_transient()
+        );
+        assert!(!Error::invalid_config("bad").is_transient());
+        assert!(!Error::NoAvailableInstance("svc".to_string()).is_transient());
+        assert!(
+            Error::Timeout {
+                operation: "register",
+                timeout: Duration::from_secs(3)
+            }
+            .is_transient()
+        );
+    }
+
+    #[test]
+    fn not_found_classification() {
+        use nacos_sdk::api::error::Error as Nacos;
+
+        let response = |ret, code| Error::Nacos(Nacos::ErrResponse(None, ret, code, None));
+        assert!(response(500, 20004).is_not_found());
+        assert!(response(500, 21008).is_not_found());
+        assert!(response(404, 0).is_not_found());
+        assert!(!response(500, 500).is_not_found());
+        assert!(!Error::Nacos(Nacos::NoAvailableServer).is_not_found());
+        assert!(!Error::NoAvailableInstance("svc".to_string()).is_not_found());
+    }
+
+    #[test]
+    fn converts_into_io_error() {
+        let io_err = io::Error::from(Error::Io(io::Error::new(io::ErrorKind::BrokenPipe, "eof")));
+        assert_eq!(io_err.kind(), io::ErrorKind::BrokenPipe);
+        assert_eq!(io_err.to_string(), "eof");
+
+        let io_err = io::Error::from(Error::invalid_config("missing required field `namespace`"));
+        assert_eq!(io_err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(
+            io_err.to_string(),
+            "invalid configuration: missing required field `namespace`"
+        );
+
+        let io_err = io::Error::from(Error::NoAvailableInstance("svc".to_string()));
+        assert_eq!(io_err.kind(), io::ErrorKind::NotFound);
+        let timeout = Error::Timeout {
+            operation: "register",
+            timeout: Duration::from_secs(3),
+        };
+        assert_eq!(io::Error::from(timeout).kind(), io::ErrorKind::TimedOut);
+        let nacos = Error::Nacos(nacos_sdk::api::error::Error::NoAvailableServer);
+        assert_eq!(io::Error::from(nacos).kind(), io::ErrorKind::Other);
+    }
+}
@@ -0,0 +1,67 @@
+use tracing::warn;
+
+use crate::manager::{ServiceManager, block_on};
+
+/// RAII guard that keeps a service instance registered for as long as it is alive.
+///
+/// Obtained from [`ServiceManager::register_guard`]. When the guard is dropped — including
+/// while unwinding from a panic — the instance is deregistered from Nacos, unless it was
+/// already deregistered by hand. Deregistration errors are logged via `tracing::warn!` and
+/// otherwise ignored.
+///
+/// Inside a tokio runtime the deregistration is spawned onto it and `drop` returns right
+/// away, so no worker is stalled on the network call. The task is lost if the runtime shuts
+/// down first, e.g. when the guard is the last thing dropped in `#[tokio::main]`; `.await`
+/// [`ServiceManager::deregister`] before returning where it must complete (the guard then
+/// does nothing). Outside a runtime, `drop` blocks on a helper thread until Nacos answers.
+#[must_use = "the instance is deregistered as soon as the guard is dropped"]
+#[derive(Debug)]
+pub struct ServiceGuard {
+    manager: ServiceManager,
+}
+
+impl ServiceGuard {
+    pub(crate) fn new(manager: ServiceManager) -> Self {
+        Self { manager }
+    }
+
+    /// The manager whose instance this guard keeps registered.
+    pub fn manager(&self) -> &ServiceManager {
+        &self.manager
+    }
+}
+
+impl Drop for ServiceGuard {
+    fn drop(&mut self) {
+        if !self.manager.registered() {
+            return;
+        }
+        let manager = self.manager.clone();
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            handle.spawn(async move {
+                if let Err(err) = manager.deregister().await {
+                    warn!(
+                        service = %manager.service_name(),
+                        error = %err,
+                        "failed to deregister service on guard drop"
+                    );
+                }
+            });
+            return;
+        }
+        // No runtime to spawn on: block on a dedicated one.
+        let result = std::thread::spawn(move || block_on(manager.deregister())).join();
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => warn!(
+                service = %self.manager.service_name(),
+                error = %err,
+                "failed to deregister service on guard drop"
+            ),
+            Err(_) => warn!(
+                service = %self.manager.service_name(),
+                "deregister thread panicked on guard drop"
+            ),
+        }
+    }
+}
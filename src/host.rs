@@ -0,0 +1,195 @@
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+
+use tracing::{debug, warn};
+
+use crate::error::{Error, Result};
+
+/// How to pick the advertised host when none is configured explicitly.
+///
+/// Set via [`ServiceConfigBuilder::host_strategy`](crate::ServiceConfigBuilder::host_strategy);
+/// an explicit [`service_host`](crate::ServiceConfigBuilder::service_host) always wins.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HostStrategy {
+    /// Whatever address the OS routes outbound traffic through (the default). Falls back to
+    /// `127.0.0.1`, with a warning, when no address can be detected (e.g. in slim containers).
+    #[default]
+    LocalIp,
+    /// The IPv6 address the OS routes outbound traffic through, for IPv6-only and dual-stack
+    /// clusters. Falls back to [`LocalIp`](Self::LocalIp) when the host has no IPv6 route.
+    LocalIpv6,
+    /// The first address bound to the named network interface, e.g. `eth0`.
+    Interface(String),
+    /// The first local address inside the given network, e.g. `10.0.0.0/8`.
+    Cidr {
+        /// Network address.
+        network: IpAddr,
+        /// Prefix length in bits.
+        prefix_len: u8,
+    },
+}
+
+impl HostStrategy {
+    /// Resolve the strategy against the host's network interfaces.
+    pub(crate) fn resolve(&self) -> Result<String> {
+        let ip = match self {
+            Self::LocalIp => local_ip_or_loopback(local_ip_address::local_ip()),
+            Self::LocalIpv6 => local_ip_address::local_ipv6().unwrap_or_else(|err| {
+                debug!(error = %err, "no local IPv6 address, falling back to IPv4");
+                local_ip_or_loopback(local_ip_address::local_ip())
+            }),
+            _ => {
+                let interfaces = local_ip_address::list_afinet_netifas()?;
+                self.select(&interfaces).ok_or_else(|| {
+                    Error::invalid_config(match self {
+                        Self::Interface(name) => {
+                            let mut names: Vec<&str> =
+                                interfaces.iter().map(|(n, _)| n.as_str()).collect();
+                            names.sort_unstable();
+                            names.dedup();
+                            format!(
+                                "no address found on interface `{name}` (available: {})",
+                                names.join(", ")
+                            )
+                        }
+                        _ => format!("no local address matches {self:?}"),
+                    })
+                })?
+            }
+        };
+        Ok(ip.to_string())
+    }
+
+    /// Pick a matching address out of `(interface name, address)` pairs.
+    fn select(&self, interfaces: &[(String, IpAddr)]) -> Option<IpAddr> {
+        interfaces
+            .iter()
+            .find(|(name, ip)| match self {
+                Self::LocalIp | Self::LocalIpv6 => false,
+                Self::Interface(wanted) => name == wanted,
+                Self::Cidr {
+                    network,
+                    prefix_len,
+                } => in_network(*ip, *network, *prefix_len),
+            })
+            .map(|(_, ip)| *ip)
+    }
+}
+
+/// User-supplied host lookup, set via
+/// [`ServiceConfigBuilder::host_resolver`](crate::ServiceConfigBuilder::host_resolver).
+#[derive(Clone)]
+pub(crate) struct HostResolver(Arc<dyn Fn() -> Result<String> + Send + Sync>);
+
+impl HostResolver {
+    pub(crate) fn new(resolve: impl Fn() -> Result<String> + Send + Sync + 'static) -> Self {
+        Self(Arc::new(resolve))
+    }
+
+    pub(crate) fn resolve(&self) -> Result<String> {
+        (self.0)()
+    }
+}
+
+impl fmt::Debug for HostResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("HostResolver(..)")
+    }
+}
+
+/// The detected local IP, or `127.0.0.1` when detection failed.
+fn local_ip_or_loopback(detected: Result<IpAddr, local_ip_address::Error>) -> IpAddr {
+    detected.unwrap_or_else(|err| {
+        warn!(
+            error = %err,
+            "could not detect the local IP, advertising 127.0.0.1; set `service_host` to a reachable address"
+        );
+        IpAddr::V4(Ipv4Addr::LOCALHOST)
+    })
+}
+
+/// Whether `ip` falls inside `network/prefix_len`; addresses of different families never match.
+fn in_network(ip: IpAddr, network: IpAddr, prefix_len: u8) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(net)) => {
+            prefix_matches(u32::from(ip).into(), u32::from(net).into(), prefix_len, 32)
+        }
+        (IpAddr::V6(ip), IpAddr::V6(net)) => {
+            prefix_matches(u128::from(ip), u128::from(net), prefix_len, 128)
+        }
+        _ => false,
+    }
+}
+
+fn prefix_matches(ip: u128, net: u128, prefix_len: u8, bits: u32) -> bool {
+    let prefix_len = u32::from(prefix_len).min(bits);
+    if prefix_len == 0 {
+        return true;
+    }
+    let shift = bits - prefix_len;
+    (ip >> shift) == (net >> shift)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    fn interfaces() -> Vec<(String, IpAddr)> {
+        vec![
+            ("docker0".to_string(), "172.17.0.1".parse().unwrap()),
+            ("eth0".to_string(), "10.1.2.3".parse().unwrap()),
+            ("eth0".to_string(), "fd00::3".parse().unwrap()),
+        ]
+    }
+
+    #[test]
+    fn select_by_interface_name() {
+        let strategy = HostStrategy::Interface("eth0".to_string());
+        assert_eq!(
+            strategy.select(&interfaces()),
+            Some("10.1.2.3".parse().unwrap())
+        );
+        let missing = HostStrategy::Interface("wlan0".to_string());
+        assert_eq!(missing.select(&interfaces()), None);
+    }
+
+    #[test]
+    fn select_by_cidr() {
+        let strategy = HostStrategy::Cidr {
+            network: "10.0.0.0".parse().unwrap(),
+            prefix_len: 8,
+        };
+        assert_eq!(
+            strategy.select(&interfaces()),
+            Some("10.1.2.3".parse().unwrap())
+        );
+        let v6 = HostStrategy::Cidr {
+            network: "fd00::".parse().unwrap(),
+            prefix_len: 8,
+        };
+        assert_eq!(v6.select(&interfaces()), Some("fd00::3".parse().unwrap()));
+    }
+
+    #[test]
+    fn local_ip_falls_back_to_loopback() {
+        let detected: IpAddr = "10.1.2.3".parse().unwrap();
+        assert_eq!(local_ip_or_loopback(Ok(detected)), detected);
+        assert_eq!(
+            local_ip_or_loopback(Err(local_ip_address::Error::LocalIpAddressNotFound)),
+            IpAddr::V4(Ipv4Addr::LOCALHOST)
+        );
+    }
+
+    #[test]
+    fn in_network_edges() {
+        let ip: IpAddr = "192.168.1.10".parse().unwrap();
+        assert!(in_network(ip, "192.168.1.0".parse().unwrap(), 24));
+        assert!(!in_network(ip, "192.168.2.0".parse().unwrap(), 24));
+        assert!(in_network(ip, "0.0.0.0".parse().unwrap(), 0));
+        assert!(in_network(ip, ip, 32));
+        assert!(!in_network(ip, "::".parse().unwrap(), 0));
+    }
+}
@@ -0,0 +1,121 @@
+use nacos_sdk::api::naming::ServiceInstance;
+
+use crate::config::{META_GRPC_PORT, META_HTTP_PORT, META_PROTOCOL, META_ZONE};
+use crate::port::Port;
+
+/// Convenience accessors on discovered [`ServiceInstance`]s, so lookup results can be dialed
+/// without parsing metadata by hand.
+///
+/// ```
+/// use ez_rust_discovery::{InstanceExt, ServiceInstance};
+///
+/// let instance = ServiceInstance {
+///     ip: "10.0.0.1".to_string(),
+///     port: 9000,
+///     ..Default::default()
+/// };
+/// assert_eq!(instance.endpoint(), "10.0.0.1:9000");
+/// ```
+pub trait InstanceExt {
+    /// `ip:port`, with IPv6 addresses wrapped in brackets (`[::1]:9000`).
+    fn endpoint(&self) -> String;
+
+    /// The port published under [`META_GRPC_PORT`], if present and a valid port number.
+    fn grpc_port(&self) -> Option<u16>;
+
+    /// The port published under [`META_HTTP_PORT`], if present and a valid port number.
+    fn http_port(&self) -> Option<u16>;
+
+    /// The cluster the instance belongs to, if set.
+    fn cluster(&self) -> Option<&str>;
+
+    /// The zone published under [`META_ZONE`], if any.
+    fn zone(&self) -> Option<&str>;
+
+    /// The transport published under [`META_PROTOCOL`], if any.
+    fn protocol(&self) -> Option<&str>;
+
+    /// The metadata value stored under `key`, if any.
+    fn meta(&self, key: &str) -> Option<&str>;
+}
+
+impl InstanceExt for ServiceInstance {
+    fn endpoint(&self) -> String {
+        if self.ip.contains(':') && !self.ip.starts_with('[') {
+            format!("[{}]:{}", self.ip, self.port)
+        } else {
+            format!("{}:{}", self.ip, self.port)
+        }
+    }
+
+    fn grpc_port(&self) -> Option<u16> {
+        let port = Port::parse(self.meta(META_GRPC_PORT)?, META_GRPC_PORT).ok()?;
+        Some(port.get())
+    }
+
+    fn http_port(&self) -> Option<u16> {
+        let port = Port::parse(self.meta(META_HTTP_PORT)?, META_HTTP_PORT).ok()?;
+        Some(port.get())
+    }
+
+    fn cluster(&self) -> Option<&str> {
+        self.cluster_name.as_deref().filter(|name| !name.is_empty())
+    }
+
+    fn zone(&self) -> Option<&str> {
+        self.meta(META_ZONE)
+    }
+
+    fn protocol(&self) -> Option<&str> {
+        self.meta(META_PROTOCOL)
+    }
+
+    fn meta(&self, key: &str) -> Option<&str> {
+        self.metadata.get(key).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, clippy::expect_used)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn instance(ip: &str, metadata: &[(&str, &str)]) -> ServiceInstance {
+        ServiceInstance {
+            ip: ip.to_string(),
+            port: 9000,
+            metadata: metadata
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn endpoint_brackets_ipv6() {
+        assert_eq!(instance("10.0.0.1", &[]).endpoint(), "10.0.0.1:9000");
+        assert_eq!(instance("fe80::1", &[]).endpoint(), "[fe80::1]:9000");
+    }
+
+    #[test]
+    fn metadata_accessors() {
+        let inst = instance(
+            "10.0.0.1",
+            &[
+                (META_GRPC_PORT, "9000"),
+                (META_HTTP_PORT, "http"),
+                (META_ZONE, "a"),
+                (META_PROTOCOL, "grpcs"),
+            ],
+        );
+        assert_eq!(inst.grpc_port(), Some(9000));
+        assert_eq!(inst.http_port(), None);
+        assert_eq!(inst.zone(), Some("a"));
+        assert_eq!(inst.protocol(), Some("grpcs"));
+        assert_eq!(inst.meta("missing"), None);
+        assert_eq!(inst.cluster(), Some("DEFAULT"));
+    }
+}
@@ -3,13 +3,50 @@
 #![doc = "Ergonomic service discovery client for Nacos, optimized for gRPC/HTTP workloads."]
 #![doc = ""]
 #![doc = "See the project [README](https://github.com/zlx2019/ez-rust-discovery) for usage examples."]
+#![doc = ""]
+#![doc = "Diagnostics are emitted through `tracing` under the `ez_rust_discovery` target: routine"]
+#![doc = "operations at `debug`, recoverable failures at `warn`. Without a subscriber nothing is"]
+#![doc = "recorded; with one, filter the target (e.g. `ez_rust_discovery=warn`) to tune verbosity."]
 
+mod balance;
+mod cache;
 mod config;
+#[cfg(feature = "consul")]
+mod consul;
 mod error;
+mod guard;
+mod host;
+mod instance;
 mod manager;
+mod metrics;
+#[cfg(any(test, feature = "test-util"))]
+mod mock;
+mod port;
+mod registration;
+mod registry;
+#[cfg(feature = "signal")]
+mod signal;
+mod subscription;
 
+pub use balance::{LoadBalancer, RandomBalancer, RoundRobinBalancer, WeightedBalancer};
 pub use config::{
-    DEFAULT_GROUP, DEFAULT_WEIGHT, META_GRPC_PORT, ServiceConfig, ServiceConfigBuilder, env_keys,
+    DEFAULT_GROUP, DEFAULT_WEIGHT, META_GRPC_PORT, META_HTTP_PORT, META_PROTOCOL, META_ZONE,
+    ServiceConfig, ServiceConfigBuilder, clear_default_group, default_group, env_keys,
+    set_default_group,
 };
+#[cfg(feature = "consul")]
+pub use consul::ConsulRegistry;
 pub use error::{Error, Result};
+pub use guard::ServiceGuard;
+pub use host::HostStrategy;
+pub use instance::InstanceExt;
 pub use manager::ServiceManager;
+pub use metrics::Metrics;
+#[cfg(any(test, feature = "test-util"))]
+pub use mock::MockRegistry;
+pub use nacos_sdk::api::naming::ServiceInstance;
+pub use registration::Registration;
+pub use registry::{NacosRegistry, Registry};
+#[cfg(feature = "signal")]
+pub use signal::{block_until_shutdown_signal, shutdown_signal};
+pub use subscription::InstanceCallback;
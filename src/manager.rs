@@ -1,142 +1,1172 @@
+use std::collections::HashMap;
 use std::future::Future;
-use std::sync::Arc;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::{Duration, Instant};
 
-use nacos_sdk::api::naming::{NamingService, NamingServiceBuilder, ServiceInstance};
-use nacos_sdk::api::props::ClientProps;
-use tracing::{debug, info};
+use nacos_sdk::api::naming::{NamingService, ServiceInstance};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::MissedTickBehavior;
+use tracing::{Instrument, debug, debug_span, warn};
 
-use crate::config::ServiceConfig;
+use crate::balance::{self, LoadBalancer, RoundRobin, SmoothWeighted};
+use crate::cache::InstanceCache;
+use crate::config::{self, ServiceConfig};
 use crate::error::{Error, Result};
+use crate::guard::ServiceGuard;
+use crate::metrics::Metrics;
+use crate::registration::Registration;
+use crate::registry::{NacosRegistry, Registry};
 
 /// Entry point for service registration and deregistration.
 ///
-/// A [`ServiceManager`] owns a Nacos `NamingService` client together with a prepared
+/// A [`ServiceManager`] owns a [`Registry`] client (Nacos by default) together with a prepared
 /// [`ServiceInstance`]; call [`register`](Self::register) /
 /// [`deregister`](Self::deregister) to bring the instance up or down.
 ///
 /// The type is `Clone` and shares its inner state through `Arc`, so it can be cheaply
-/// passed across tasks.
+/// passed across tasks. The instance itself sits behind a lock in that shared state: a
+/// setter such as [`set_weight`](Self::set_weight) called on one clone is seen by
+/// [`instance`](Self::instance) on every other.
 #[derive(Clone)]
 pub struct ServiceManager {
     inner: Arc<Inner>,
 }
 
 struct Inner {
-    naming: NamingService,
-    instance: ServiceInstance,
+    registry: Arc<dyn Registry>,
+    naming: Option<NamingService>,
+    /// Nacos connection settings, kept to open clients for other namespaces on demand.
+    connection: Option<ServiceConfig>,
+    namespaces: Mutex<HashMap<String, Arc<dyn Registry>>>,
+    instance: RwLock<ServiceInstance>,
+    registered: AtomicBool,
+    /// Serializes the calls that register, deregister or re-push the instance, so a setter or
+    /// keepalive check racing with `deregister` cannot put the instance back.
+    lifecycle: tokio::sync::Mutex<()>,
     service_name: String,
     group: String,
+    extra_groups: Vec<String>,
+    service_host: String,
+    service_port: u16,
+    timeout: Option<Duration>,
+    keepalive: Option<Duration>,
+    watchdog: Mutex<Option<JoinHandle<()>>>,
+    discovery_only: bool,
+    cache_ttl: Option<Duration>,
+    register_jitter: Option<Duration>,
+    jittered: AtomicBool,
+    cache: InstanceCache,
+    round_robin: RoundRobin,
+    smooth_weighted: SmoothWeighted,
+    metrics: Metrics,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        if let Some(watchdog) = self
+            .watchdog
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+        {
+            watchdog.abort();
+        }
+    }
 }
 
 impl ServiceManager {
     /// Asynchronously construct a [`ServiceManager`] from a [`ServiceConfig`].
     ///
-    /// This only initializes the underlying Nacos client; the instance is **not** registered
-    /// until [`register`](Self::register) is called.
+    /// The configuration is [validated](ServiceConfig::validate) first. This only initializes
+    /// the underlying Nacos client; the instance is **not** registered until
+    /// [`register`](Self::register) is called.
+    ///
+    /// With the `tls` feature enabled, the client connects to Nacos over TLS.
     pub async fn new(config: ServiceConfig) -> Result<Self> {
+        config.validate()?;
         debug!(
             nacos_addr = %config.nacos_addr,
+            endpoint = ?config.endpoint,
             namespace = %config.namespace,
             service = %config.service_name,
             service_host = %config.service_host,
             service_port = config.service_port,
             auth = config.auth.is_some(),
+            access_key = config.access_key.is_some(),
+            tls = cfg!(feature = "tls"),
             "creating ServiceManager"
         );
 
-        let mut client_props = ClientProps::new()
-            .server_addr(&config.nacos_addr)
-            .namespace(&config.namespace);
+        let registry = NacosRegistry::connect(&config).await?;
+        let naming = registry.naming_service().clone();
+        let connection = config.clone();
+        Ok(Self::from_parts(
+            config,
+            Arc::new(registry),
+            Some(naming),
+            Some(connection),
+        ))
+    }
 
-        let auth_enabled = config.auth.is_some();
-        if let Some((user, pass)) = config.auth.as_ref() {
-            client_props = client_props.auth_username(user).auth_password(pass);
-        }
+    /// Like [`new`](Self::new), but also [`ping`](Self::ping) the registry before returning,
+    /// so a successful return means Nacos is reachable rather than merely configured.
+    pub async fn connect(config: ServiceConfig) -> Result<Self> {
+        let manager = Self::new(config).await?;
+        manager.ping().await?;
+        Ok(manager)
+    }
 
-        let mut builder = NamingServiceBuilder::new(client_props);
-        if auth_enabled {
-            // Enable the HTTP auth plugin; otherwise username/password would be ignored.
-            builder = builder.enable_auth_plugin_http();
-        }
-        let naming = builder.build().await?;
+    /// Construct a [`ServiceManager`] entirely from environment variables.
+    ///
+    /// Shorthand for `ServiceManager::new(ServiceConfig::from_env()?)`. Requires
+    /// [`NACOS_ADDR`](crate::env_keys::NACOS_ADDR),
+    /// [`SERVICE_NAME`](crate::env_keys::SERVICE_NAME) and either
+    /// [`SERVICE_ADDR`](crate::env_keys::SERVICE_ADDR) or
+    /// [`SERVICE_PORT`](crate::env_keys::SERVICE_PORT); see [`ServiceConfig::from_env`] for
+    /// the optional ones. Use [`new`](Self::new) to mix code and environment configuration.
+    pub async fn from_env() -> Result<Self> {
+        Self::new(ServiceConfig::from_env()?).await
+    }
+
+    /// Construct a [`ServiceManager`] on top of an arbitrary [`Registry`] backend.
+    ///
+    /// The Nacos connection fields of `config` (`nacos_addr`, `namespace`, `auth`,
+    /// `access_key`) are ignored; everything describing the instance is used as usual.
+    /// Operations the backend does not implement, such as [`subscribe`](Self::subscribe) on
+    /// some registries, return [`Error::Unsupported`].
+    pub fn with_registry(config: ServiceConfig, registry: impl Registry) -> Result<Self> {
+        config.validate_instance()?;
+        Ok(Self::from_parts(config, Arc::new(registry), None, None))
+    }
+
+    /// Construct a [`ServiceManager`] on a `NamingService` the caller already built, e.g. with
+    /// client properties this crate does not expose.
+    ///
+    /// Like [`with_registry`](Self::with_registry), the Nacos connection fields of `config`
+    /// are ignored and only the instance fields are used; the service keeps whatever
+    /// namespace and credentials it was built with.
+    /// [`get_instances_in_namespace`](Self::get_instances_in_namespace) is unsupported, since
+    /// the connection settings needed to reach another namespace are unknown.
+    pub fn with_naming_service(config: ServiceConfig, naming: NamingService) -> Result<Self> {
+        config.validate_instance()?;
+        let registry = NacosRegistry::new(naming.clone());
+        Ok(Self::from_parts(
+            config,
+            Arc::new(registry),
+            Some(naming),
+            None,
+        ))
+    }
 
-        let instance = ServiceInstance {
+    fn from_parts(
+        config: ServiceConfig,
+        registry: Arc<dyn Registry>,
+        naming: Option<NamingService>,
+        connection: Option<ServiceConfig>,
+    ) -> Self {
+        let mut instance = ServiceInstance {
+            instance_id: config.instance_id,
             ip: config.service_host.clone(),
             port: i32::from(config.service_port),
             weight: config.weight,
             healthy: true,
-            enabled: true,
+            enabled: config.enabled,
             ephemeral: config.ephemeral,
             metadata: config.metadata.clone(),
             ..Default::default()
         };
+        if let Some(cluster_name) = config.cluster_name {
+            instance.cluster_name = Some(cluster_name);
+        }
 
-        Ok(Self {
+        Self {
             inner: Arc::new(Inner {
+                registry,
                 naming,
-                instance,
+                connection,
+                namespaces: Mutex::default(),
+                instance: RwLock::new(instance),
+                registered: AtomicBool::new(false),
+                lifecycle: tokio::sync::Mutex::new(()),
                 service_name: config.service_name,
                 group: config.group,
+                extra_groups: config.extra_groups,
+                service_host: config.service_host,
+                service_port: config.service_port,
+                timeout: config.timeout,
+                keepalive: config.keepalive,
+                watchdog: Mutex::default(),
+                discovery_only: config.discovery_only,
+                cache_ttl: config.cache_ttl,
+                register_jitter: config.register_jitter,
+                jittered: AtomicBool::new(false),
+                cache: InstanceCache::default(),
+                round_robin: RoundRobin::default(),
+                smooth_weighted: SmoothWeighted::default(),
+                metrics: Metrics::default(),
             }),
-        })
+        }
     }
 
     /// Synchronously construct a [`ServiceManager`] using a temporary tokio runtime.
     ///
-    /// Intended for purely synchronous programs. From an async context, prefer
-    /// [`new`](Self::new) instead.
+    /// Intended for purely synchronous programs. From an async context, use
+    /// [`new`](Self::new) instead; calling this inside a tokio runtime returns an error.
+    #[cfg(feature = "blocking")]
     pub fn new_blocking(config: ServiceConfig) -> Result<Self> {
         block_on(Self::new(config))
     }
 
     /// Register the service instance with Nacos.
+    ///
+    /// Fails with [`Error::InvalidConfig`] on a
+    /// [discovery-only](crate::ServiceConfigBuilder::discovery_only) manager.
+    ///
+    /// With a [`keepalive`](crate::ServiceConfigBuilder::keepalive) interval configured this
+    /// also starts the re-registration watchdog. The watchdog runs on the current tokio
+    /// runtime, so it does not outlive a `register_blocking` call.
+    ///
+    /// With a [`register_jitter`](crate::ServiceConfigBuilder::register_jitter) configured,
+    /// the first call first sleeps for a random part of it.
     pub async fn register(&self) -> Result<()> {
-        self.inner
-            .naming
-            .register_instance(
-                self.inner.service_name.clone(),
-                Some(self.inner.group.clone()),
-                self.inner.instance.clone(),
-            )
+        self.ensure_registrable("register")?;
+        self.startup_jitter().await;
+        let _lifecycle = self.inner.lifecycle.lock().await;
+        self.register_instance(&self.inner.service_name, self.instance_snapshot())
             .await?;
-        info!(
-            service = %self.inner.service_name,
-            group = %self.inner.group,
-            host = %self.inner.instance.ip,
-            port = self.inner.instance.port,
-            "service registered"
+        self.inner.registered.store(true, Ordering::Release);
+        self.start_watchdog();
+        Ok(())
+    }
+
+    /// Like [`register`](Self::register), but report what was registered, including the
+    /// instance id the registry assigned.
+    ///
+    /// The id is looked up with one extra query after registering; if that query fails the
+    /// registration still succeeds and the id falls back to the configured one, if any.
+    pub async fn register_detailed(&self) -> Result<Registration> {
+        self.register().await?;
+        let own = self.instance_snapshot();
+        let listed = match self
+            .fetch_instances(&self.inner.service_name, &self.inner.group)
+            .await
+        {
+            Ok(instances) => instances
+                .into_iter()
+                .find(|i| same_endpoint(i, &own))
+                .and_then(|i| i.instance_id),
+            Err(err) => {
+                debug!(
+                    service = %self.inner.service_name,
+                    error = %err,
+                    "could not look up the registered instance id"
+                );
+                None
+            }
+        };
+        Ok(Registration {
+            instance_id: listed.or(own.instance_id),
+            service_name: self.inner.service_name.clone(),
+            group: self.inner.group.clone(),
+            host: own.ip,
+            port: self.inner.service_port,
+        })
+    }
+
+    /// Register an arbitrary `instance` under `service_name` in this manager's group, and in
+    /// each of its [`extra_groups`](crate::ServiceConfigBuilder::extra_groups).
+    ///
+    /// Lets one manager own several registrations, e.g. an HTTP and a gRPC facade of the same
+    /// process. [`register`](Self::register) is the shorthand for the primary instance. Every
+    /// group is attempted; if any fail, the first failure is returned.
+    pub async fn register_instance(
+        &self,
+        service_name: &str,
+        instance: ServiceInstance,
+    ) -> Result<()> {
+        let result = self
+            .in_all_groups("register", |group| {
+                self.register_in(group, service_name, instance.clone())
+            })
+            .await;
+        self.inner
+            .metrics
+            .record_register(service_name, &instance, result.is_ok());
+        result
+    }
+
+    async fn register_in(
+        &self,
+        group: &str,
+        service_name: &str,
+        instance: ServiceInstance,
+    ) -> Result<()> {
+        let span = debug_span!(
+            "register",
+            service = %service_name,
+            group = %group,
+            host = %instance.ip,
+            port = instance.port,
         );
+        let registry = &self.inner.registry;
+        let started = Instant::now();
+        self.with_timeout("register", registry.register(service_name, group, instance))
+            .instrument(span.clone())
+            .await
+            .inspect_err(|err| {
+                span.in_scope(
+                    || debug!(elapsed = ?started.elapsed(), error = %err, "register failed"),
+                );
+            })?;
+        span.in_scope(|| debug!(elapsed = ?started.elapsed(), "service registered"));
         Ok(())
     }
 
+    /// Register many instances at once, e.g. for a process hosting several virtual services.
+    ///
+    /// Instances are grouped by service name; each service is registered with a single batch
+    /// call where the registry supports it, and different services are registered
+    /// concurrently. Every call is bounded by the configured
+    /// [`timeout`](crate::ServiceConfigBuilder::timeout). All batches run to completion; if
+    /// any fail, the failures are logged and the first one is returned.
+    ///
+    /// Like [`register_instance`](Self::register_instance), every batch is registered in the
+    /// primary group and each of the [`extra_groups`](crate::ServiceConfigBuilder::extra_groups);
+    /// a failure in one group is logged and does not skip the others.
+    ///
+    /// With Nacos, a batch replaces the instances this client previously batch-registered for
+    /// that service.
+    pub async fn register_batch(&self, instances: Vec<(String, ServiceInstance)>) -> Result<()> {
+        let mut by_service: Vec<(String, Vec<ServiceInstance>)> = Vec::new();
+        for (service_name, instance) in instances {
+            match by_service
+                .iter_mut()
+                .find(|(name, _)| *name == service_name)
+            {
+                Some((_, batch)) => batch.push(instance),
+                None => by_service.push((service_name, vec![instance])),
+            }
+        }
+
+        let mut tasks = JoinSet::new();
+        let groups: Vec<String> = std::iter::once(&self.inner.group)
+            .chain(&self.inner.extra_groups)
+            .cloned()
+            .collect();
+        for (service_name, batch) in by_service {
+            let registry = Arc::clone(&self.inner.registry);
+            let groups = groups.clone();
+            let timeout = self.inner.timeout;
+            tasks.spawn(async move {
+                let mut result = Ok(());
+                for group in &groups {
+                    let call = registry.register_batch(&service_name, group, batch.clone());
+                    if let Err(err) = bounded(timeout, "register_batch", call).await {
+                        if groups.len() > 1 {
+                            warn!(group = %group, error = %err, "register_batch failed in group");
+                        }
+                        if result.is_ok() {
+                            result = Err(err);
+                        }
+                    }
+                }
+                (service_name, batch, result)
+            });
+        }
+
+        let mut first_err = None;
+        while let Some(joined) = tasks.join_next().await {
+            let (service_name, batch, result) =
+                joined.map_err(|err| Error::Io(io::Error::other(err)))?;
+            for instance in &batch {
+                self.inner
+                    .metrics
+                    .record_register(&service_name, instance, result.is_ok());
+            }
+            let count = batch.len();
+            match result {
+                Ok(()) => debug!(service = %service_name, count, "service batch registered"),
+                Err(err) => {
+                    warn!(service = %service_name, count, error = %err, "batch register failed");
+                    first_err.get_or_insert(err);
+                }
+            }
+        }
+        first_err.map_or(Ok(()), Err)
+    }
+
+    /// Register the service instance, retrying failures with exponential backoff and jitter.
+    ///
+    /// Makes at most `max_attempts` attempts (at least one). Before retry `n` (1-based) it
+    /// sleeps for a random duration between half and all of `base_delay * 2^(n - 1)`. Only
+    /// [transient](Error::is_transient) errors are retried; others, and the last error once
+    /// attempts run out, are returned as-is.
+    pub async fn register_with_retry(&self, max_attempts: u32, base_delay: Duration) -> Result<()> {
+        let max_attempts = max_attempts.max(1);
+        let mut attempt = 1;
+        loop {
+            match self.register().await {
+                Ok(()) => return Ok(()),
+                Err(err) if attempt >= max_attempts || !err.is_transient() => return Err(err),
+                Err(err) => {
+                    let delay = backoff_delay(base_delay, attempt, rand::random());
+                    warn!(
+                        service = %self.inner.service_name,
+                        attempt,
+                        max_attempts,
+                        retry_in = ?delay,
+                        error = %err,
+                        "register failed, retrying"
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
     /// Deregister the service instance from Nacos.
+    ///
+    /// Idempotent: if Nacos reports the instance as not found, the call succeeds.
+    ///
+    /// Fails with [`Error::InvalidConfig`] on a
+    /// [discovery-only](crate::ServiceConfigBuilder::discovery_only) manager. Stops the
+    /// [`keepalive`](crate::ServiceConfigBuilder::keepalive) watchdog, if any.
     pub async fn deregister(&self) -> Result<()> {
+        self.ensure_registrable("deregister")?;
+        self.stop_watchdog();
+        let _lifecycle = self.inner.lifecycle.lock().await;
+        // Clear the flag up front so a keepalive check racing with us does not re-register.
+        let was_registered = self.inner.registered.swap(false, Ordering::AcqRel);
+        self.deregister_instance(&self.inner.service_name, self.instance_snapshot())
+            .await
+            .inspect_err(|_| {
+                self.inner
+                    .registered
+                    .store(was_registered, Ordering::Release);
+            })
+    }
+
+    /// Gracefully take the instance out of service: disable it so no new traffic is routed
+    /// here, wait `wait` for in-flight requests to finish, then deregister.
+    ///
+    /// Once deregistered, the stored instance is re-enabled so a later
+    /// [`register`](Self::register) starts out serving. If deregistration fails the instance
+    /// stays registered but disabled.
+    pub async fn drain(&self, wait: Duration) -> Result<()> {
+        self.ensure_registrable("drain")?;
+        let enabled = self.instance().enabled;
+        self.set_enabled(false).await?;
+        debug!(service = %self.inner.service_name, ?wait, "instance disabled, draining");
+        tokio::time::sleep(wait).await;
+        self.deregister().await?;
+        self.update_instance(|instance| instance.enabled = enabled)
+            .await
+    }
+
+    /// Mark the instance healthy or unhealthy without deregistering it.
+    ///
+    /// If the instance is currently registered the change is pushed to Nacos by
+    /// re-registering; otherwise it takes effect on the next [`register`](Self::register).
+    /// Handy for readiness probes that take the instance out of rotation temporarily.
+    pub async fn set_healthy(&self, healthy: bool) -> Result<()> {
+        self.update_instance(|instance| instance.healthy = healthy)
+            .await
+    }
+
+    /// Change the instance weight, e.g. to drain traffic during an incident.
+    ///
+    /// The weight must be finite and non-negative. Like [`set_healthy`](Self::set_healthy),
+    /// the change is pushed by re-registering when registered and otherwise applies on the
+    /// next [`register`](Self::register).
+    pub async fn set_weight(&self, weight: f64) -> Result<()> {
+        config::validate_weight(weight)?;
+        self.update_instance(|instance| instance.weight = weight)
+            .await
+    }
+
+    /// Set the metadata entry `key` to `value`, or remove it when `value` is `None`, e.g. to
+    /// flag `draining=true` so clients stop routing here ahead of deregistration.
+    ///
+    /// Like [`set_healthy`](Self::set_healthy), the change is pushed by re-registering when
+    /// registered and otherwise applies on the next [`register`](Self::register).
+    pub async fn update_metadata(&self, key: &str, value: Option<String>) -> Result<()> {
+        if key.trim().is_empty() {
+            return Err(Error::invalid_config("metadata key must not be empty"));
+        }
+        if let (config::META_PROTOCOL, Some(protocol)) = (key, &value) {
+            config::validate_protocol(protocol)?;
+        }
+        self.update_instance(|instance| match value {
+            Some(value) => {
+                instance.metadata.insert(key.to_string(), value);
+            }
+            None => {
+                instance.metadata.remove(key);
+            }
+        })
+        .await
+    }
+
+    /// Switch the instance between ephemeral and persistent.
+    ///
+    /// Nacos keeps the two kinds apart, so a registered instance is deregistered under its old
+    /// kind before being re-registered under the new one; otherwise the change applies on the
+    /// next [`register`](Self::register). The [`keepalive`](crate::ServiceConfigBuilder::keepalive)
+    /// watchdog follows the kind: it stops for persistent instances and restarts for
+    /// registered ephemeral ones.
+    pub async fn set_ephemeral(&self, ephemeral: bool) -> Result<()> {
+        let _lifecycle = self.inner.lifecycle.lock().await;
+        let previous = self.instance_snapshot();
+        if previous.ephemeral == ephemeral {
+            return Ok(());
+        }
+        if !self.registered() {
+            self.set_instance(|instance| instance.ephemeral = ephemeral);
+            return Ok(());
+        }
+        // Take the old kind down the way `deregister` does, so the watchdog cannot bring it
+        // back while the kind is switching.
+        self.stop_watchdog();
+        self.inner.registered.store(false, Ordering::Release);
+        if let Err(err) = self
+            .deregister_instance(&self.inner.service_name, previous)
+            .await
+        {
+            self.inner.registered.store(true, Ordering::Release);
+            self.start_watchdog();
+            return Err(err);
+        }
+        let instance = self.set_instance(|instance| instance.ephemeral = ephemeral);
+        self.register_instance(&self.inner.service_name, instance)
+            .await?;
+        self.inner.registered.store(true, Ordering::Release);
+        self.start_watchdog();
+        Ok(())
+    }
+
+    /// Enable or disable the instance without deregistering it.
+    ///
+    /// A disabled instance stays listed in Nacos but receives no traffic, which suits
+    /// blue/green switches. Like [`set_healthy`](Self::set_healthy), the change is pushed
+    /// immediately when registered and otherwise applies on the next
+    /// [`register`](Self::register).
+    pub async fn set_enabled(&self, enabled: bool) -> Result<()> {
+        self.update_instance(|instance| instance.enabled = enabled)
+            .await
+    }
+
+    /// Deregister an `instance` previously registered under `service_name` via
+    /// [`register_instance`](Self::register_instance), from every group it was registered in.
+    ///
+    /// The instance need not have been registered by this manager: after an unclean shutdown,
+    /// a restarted process can rebuild its old instance from the persisted id, host and port
+    /// and remove it instead of waiting for Nacos to expire it. Nacos matches the instance by
+    /// host, port and cluster, and its `ephemeral` flag must match the original registration.
+    /// Like [`deregister`](Self::deregister), an instance Nacos no longer knows counts as
+    /// success.
+    pub async fn deregister_instance(
+        &self,
+        service_name: &str,
+        instance: ServiceInstance,
+    ) -> Result<()> {
+        let result = self
+            .in_all_groups("deregister", |group| {
+                self.deregister_in(group, service_name, instance.clone())
+            })
+            .await;
         self.inner
-            .naming
-            .deregister_instance(
-                self.inner.service_name.clone(),
-                Some(self.inner.group.clone()),
-                self.inner.instance.clone(),
+            .metrics
+            .record_deregister(service_name, &instance, result.is_ok());
+        result
+    }
+
+    async fn deregister_in(
+        &self,
+        group: &str,
+        service_name: &str,
+        instance: ServiceInstance,
+    ) -> Result<()> {
+        let span = debug_span!(
+            "deregister",
+            service = %service_name,
+            group = %group,
+            host = %instance.ip,
+            port = instance.port,
+        );
+        let registry = &self.inner.registry;
+        let started = Instant::now();
+        let result = self
+            .with_timeout(
+                "deregister",
+                registry.deregister(service_name, group, instance),
             )
+            .instrument(span.clone())
+            .await;
+        match result {
+            Ok(()) => {
+                span.in_scope(|| debug!(elapsed = ?started.elapsed(), "service deregistered"));
+                Ok(())
+            }
+            // Already gone, e.g. a manual deregister followed by a guard's: nothing to undo.
+            Err(err) if err.is_not_found() => {
+                span.in_scope(|| debug!(error = %err, "instance already deregistered"));
+                Ok(())
+            }
+            Err(err) => {
+                span.in_scope(
+                    || debug!(elapsed = ?started.elapsed(), error = %err, "deregister failed"),
+                );
+                Err(err)
+            }
+        }
+    }
+
+    /// Query every instance of `service_name` currently known to Nacos.
+    ///
+    /// `group` falls back to this manager's own [`group`](Self::group) when `None`. With a
+    /// [`cache_ttl`](crate::ServiceConfigBuilder::cache_ttl) configured, results are served
+    /// from cache; see there for how entries are refreshed.
+    pub async fn get_instances(
+        &self,
+        service_name: &str,
+        group: Option<String>,
+    ) -> Result<Vec<ServiceInstance>> {
+        let group = group.unwrap_or_else(|| self.inner.group.clone());
+        let Some(ttl) = self.inner.cache_ttl else {
+            return self.fetch_instances(service_name, &group).await;
+        };
+        if let Some((instances, refresh)) = self.inner.cache.lookup(service_name, &group, ttl) {
+            if refresh {
+                self.spawn_cache_refresh(service_name, &group);
+            }
+            return Ok(instances);
+        }
+        let instances = self.fetch_instances(service_name, &group).await?;
+        self.inner
+            .cache
+            .store(service_name, &group, instances.clone());
+        Ok(instances)
+    }
+
+    /// Refetch a cached instance list in the background, leaving the stale entry in place
+    /// (and eligible for another refresh) if that fails.
+    fn spawn_cache_refresh(&self, service_name: &str, group: &str) {
+        let inner = Arc::downgrade(&self.inner);
+        let service_name = service_name.to_string();
+        let group = group.to_string();
+        tokio::spawn(async move {
+            let Some(inner) = inner.upgrade() else {
+                return;
+            };
+            let manager = ServiceManager { inner };
+            match manager.fetch_instances(&service_name, &group).await {
+                Ok(instances) => manager.inner.cache.store(&service_name, &group, instances),
+                Err(err) => {
+                    manager.inner.cache.refresh_failed(&service_name, &group);
+                    warn!(service = %service_name, error = %err, "instance cache refresh failed");
+                }
+            }
+        });
+    }
+
+    /// Query the registry directly, bypassing the instance cache.
+    async fn fetch_instances(
+        &self,
+        service_name: &str,
+        group: &str,
+    ) -> Result<Vec<ServiceInstance>> {
+        let instances = self
+            .inner
+            .registry
+            .get_instances(service_name, group)
             .await?;
-        info!(
-            service = %self.inner.service_name,
-            group = %self.inner.group,
-            "service deregistered"
+        debug!(
+            service = %service_name,
+            count = instances.len(),
+            "fetched service instances"
+        );
+        Ok(instances)
+    }
+
+    /// Query every instance of `service_name` in another Nacos `namespace`, e.g. a shared
+    /// infrastructure namespace, while this manager stays registered in its own.
+    ///
+    /// A read-only client for `namespace` is connected with this manager's connection
+    /// settings on first use and reused afterwards. `group` falls back to this manager's own
+    /// [`group`](Self::group). Managers built via [`with_registry`](Self::with_registry) or
+    /// [`with_naming_service`](Self::with_naming_service) return [`Error::Unsupported`].
+    pub async fn get_instances_in_namespace(
+        &self,
+        namespace: &str,
+        service_name: &str,
+        group: Option<String>,
+    ) -> Result<Vec<ServiceInstance>> {
+        let registry = self.namespace_registry(namespace).await?;
+        let group = group.unwrap_or_else(|| self.inner.group.clone());
+        let instances = registry.get_instances(service_name, &group).await?;
+        debug!(
+            namespace = %namespace,
+            service = %service_name,
+            count = instances.len(),
+            "fetched service instances"
         );
+        Ok(instances)
+    }
+
+    /// The registry client for `namespace`, connecting and caching one if needed.
+    async fn namespace_registry(&self, namespace: &str) -> Result<Arc<dyn Registry>> {
+        let Some(connection) = &self.inner.connection else {
+            return Err(Error::Unsupported(
+                "cross-namespace lookups require a Nacos-backed manager".to_string(),
+            ));
+        };
+        if namespace == connection.namespace {
+            return Ok(Arc::clone(&self.inner.registry));
+        }
+        if let Some(registry) = self
+            .inner
+            .namespaces
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(namespace)
+        {
+            return Ok(Arc::clone(registry));
+        }
+
+        let mut config = connection.clone();
+        config.namespace = namespace.to_string();
+        let registry: Arc<dyn Registry> = Arc::new(NacosRegistry::connect(&config).await?);
+        debug!(namespace = %namespace, "connected client for namespace");
+        // Another caller may have connected concurrently; keep whichever landed first.
+        Ok(Arc::clone(
+            self.inner
+                .namespaces
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .entry(namespace.to_string())
+                .or_insert(registry),
+        ))
+    }
+
+    /// List the service names registered in `group`, paginated.
+    ///
+    /// `group` falls back to this manager's own [`group`](Self::group) when `None`; `page` is
+    /// 1-based and `page_size` must be positive. Returns the page of names together with the
+    /// total number of services in the group, e.g. for a registry dashboard.
+    pub async fn list_services(
+        &self,
+        group: Option<String>,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<String>, u32)> {
+        if page == 0 || page_size == 0 {
+            return Err(Error::invalid_config(format!(
+                "invalid page `{page}` / page_size `{page_size}`: both must be positive"
+            )));
+        }
+        let group = group.unwrap_or_else(|| self.inner.group.clone());
+        let (names, total) = self
+            .inner
+            .registry
+            .list_services(&group, page, page_size)
+            .await?;
+        debug!(group = %group, page, count = names.len(), total, "listed services");
+        Ok((names, total))
+    }
+
+    /// Check that the registry is reachable with a lightweight discovery call, e.g. right
+    /// after [`new`](Self::new) to fail fast on a misconfigured address.
+    ///
+    /// Lists a single service of this manager's group, falling back to querying its own
+    /// service when the backend cannot list services. Bounded by the configured
+    /// [`timeout`](crate::ServiceConfigBuilder::timeout).
+    pub async fn ping(&self) -> Result<()> {
+        let registry = &self.inner.registry;
+        let group = &self.inner.group;
+        let started = Instant::now();
+        self.with_timeout("ping", async {
+            match registry.list_services(group, 1, 1).await {
+                Err(Error::Unsupported(_)) => registry
+                    .get_instances(&self.inner.service_name, group)
+                    .await
+                    .map(drop),
+                result => result.map(drop),
+            }
+        })
+        .await
+        .inspect_err(|err| debug!(group = %group, error = %err, "registry ping failed"))?;
+        debug!(group = %group, elapsed = ?started.elapsed(), "registry reachable");
+        Ok(())
+    }
+
+    /// Query the instances of `service_name` in this manager's group that are both healthy
+    /// and enabled, i.e. eligible to receive traffic.
+    pub async fn get_healthy_instances(&self, service_name: &str) -> Result<Vec<ServiceInstance>> {
+        let instances = self.get_instances(service_name, None).await?;
+        Ok(healthy_only(instances))
+    }
+
+    /// Query the instances of `service_name` in this manager's group whose metadata contains
+    /// every `filters` pair, e.g. `version=2` and `zone=a` for canary routing.
+    ///
+    /// Filtering happens client-side over the full instance list; an empty `filters` matches
+    /// everything. Health is not considered; see
+    /// [`get_healthy_instances`](Self::get_healthy_instances).
+    pub async fn get_instances_by_meta(
+        &self,
+        service_name: &str,
+        filters: &HashMap<String, String>,
+    ) -> Result<Vec<ServiceInstance>> {
+        let instances = self.get_instances(service_name, None).await?;
+        Ok(instances
+            .into_iter()
+            .filter(|i| metadata_matches(i, filters))
+            .collect())
+    }
+
+    /// Pick one healthy instance of `service_name`, rotating round-robin across calls.
+    ///
+    /// Returns [`Error::NoAvailableInstance`] when no healthy instance exists.
+    pub async fn select_instance(&self, service_name: &str) -> Result<ServiceInstance> {
+        let instances = self.get_healthy_instances(service_name).await?;
+        self.inner
+            .round_robin
+            .pick(service_name, &instances)
+            .cloned()
+            .ok_or_else(|| Error::NoAvailableInstance(service_name.to_string()))
+    }
+
+    /// Like [`select_instance`](Self::select_instance), but never returns this manager's own
+    /// instance (matched by host and port), for calls between peers of the same service.
+    ///
+    /// Returns [`Error::NoAvailableInstance`] when no other healthy instance exists.
+    pub async fn select_instance_excluding_self(
+        &self,
+        service_name: &str,
+    ) -> Result<ServiceInstance> {
+        let own = self.instance_snapshot();
+        let mut instances = self.get_healthy_instances(service_name).await?;
+        instances.retain(|i| !same_endpoint(i, &own));
+        self.inner
+            .round_robin
+            .pick(service_name, &instances)
+            .cloned()
+            .ok_or_else(|| Error::NoAvailableInstance(service_name.to_string()))
+    }
+
+    /// Pick one healthy instance of `service_name` at random, with probability proportional to
+    /// its weight. Instances with weight `0` are never selected.
+    ///
+    /// Returns [`Error::NoAvailableInstance`] when no eligible instance exists.
+    pub async fn select_weighted(&self, service_name: &str) -> Result<ServiceInstance> {
+        let instances = self.get_healthy_instances(service_name).await?;
+        balance::pick_weighted(&instances, rand::random())
+            .cloned()
+            .ok_or_else(|| Error::NoAvailableInstance(service_name.to_string()))
+    }
+
+    /// Pick one healthy instance of `service_name` with a caller-supplied [`LoadBalancer`],
+    /// e.g. one of the shipped balancers or a custom least-connections policy.
+    ///
+    /// Returns [`Error::NoAvailableInstance`] when no healthy instance exists or `balancer`
+    /// declines to choose one.
+    pub async fn select_with<L>(&self, service_name: &str, balancer: &L) -> Result<ServiceInstance>
+    where
+        L: LoadBalancer + ?Sized,
+    {
+        let instances = self.get_healthy_instances(service_name).await?;
+        balancer
+            .choose(&instances)
+            .cloned()
+            .ok_or_else(|| Error::NoAvailableInstance(service_name.to_string()))
+    }
+
+    /// Pick one healthy instance of `service_name` by smooth weighted round robin, as in
+    /// nginx: selections follow the weights exactly over short windows and are interleaved,
+    /// avoiding the bursts of [`select_weighted`](Self::select_weighted). Instances with
+    /// weight `0` are never selected.
+    ///
+    /// Returns [`Error::NoAvailableInstance`] when no eligible instance exists.
+    pub async fn select_smooth_weighted(&self, service_name: &str) -> Result<ServiceInstance> {
+        let instances = self.get_healthy_instances(service_name).await?;
+        self.inner
+            .smooth_weighted
+            .pick(service_name, &instances)
+            .cloned()
+            .ok_or_else(|| Error::NoAvailableInstance(service_name.to_string()))
+    }
+
+    /// Subscribe to instance changes of `service_name` in this manager's group.
+    ///
+    /// `callback` fires with the full, current instance list whenever the registry pushes an
+    /// update. Subscribing again to the same service replaces the previous callback.
+    pub async fn subscribe<F>(&self, service_name: &str, callback: F) -> Result<()>
+    where
+        F: Fn(Vec<ServiceInstance>) + Send + Sync + 'static,
+    {
+        // Pushed changes make cached lookups of this service stale.
+        let inner = Arc::downgrade(&self.inner);
+        let (service, group) = (service_name.to_string(), self.inner.group.clone());
+        let callback = move |instances: Vec<ServiceInstance>| {
+            if let Some(inner) = inner.upgrade() {
+                inner.cache.invalidate(&service, &group);
+            }
+            callback(instances);
+        };
+        self.inner
+            .registry
+            .subscribe(service_name, &self.inner.group, Arc::new(callback))
+            .await?;
+        debug!(service = %service_name, "subscribed to service changes");
+        Ok(())
+    }
+
+    /// Remove the callback installed by [`subscribe`](Self::subscribe) for `service_name`.
+    ///
+    /// Does nothing if the service is not subscribed.
+    pub async fn unsubscribe(&self, service_name: &str) -> Result<()> {
+        self.inner
+            .registry
+            .unsubscribe(service_name, &self.inner.group)
+            .await?;
+        debug!(service = %service_name, "unsubscribed from service changes");
         Ok(())
     }
 
+    /// Watch `service_name` in this manager's group by polling every `interval`, for networks
+    /// where the push channel behind [`subscribe`](Self::subscribe) is blocked.
+    ///
+    /// `callback` fires with the full instance list after the first successful fetch, then
+    /// whenever the set of instance ids changes. Failed polls are logged and skipped. The task
+    /// stops once the manager is dropped; abort the returned handle to stop it earlier.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero or if called outside of a tokio runtime.
+    pub fn watch_polling<F>(
+        &self,
+        service_name: &str,
+        interval: Duration,
+        callback: F,
+    ) -> JoinHandle<()>
+    where
+        F: Fn(Vec<ServiceInstance>) + Send + Sync + 'static,
+    {
+        let inner = Arc::downgrade(&self.inner);
+        let service_name = service_name.to_string();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            let mut last_ids: Option<Vec<String>> = None;
+            loop {
+                ticker.tick().await;
+                let Some(inner) = inner.upgrade() else {
+                    return;
+                };
+                let manager = ServiceManager { inner };
+                let instances = match manager
+                    .fetch_instances(&service_name, &manager.inner.group)
+                    .await
+                {
+                    Ok(instances) => instances,
+                    Err(err) if err.is_not_found() => Vec::new(),
+                    Err(err) => {
+                        warn!(service = %service_name, error = %err, "polling instances failed");
+                        continue;
+                    }
+                };
+                let mut ids: Vec<String> = instances.iter().map(instance_key).collect();
+                ids.sort_unstable();
+                if last_ids.as_ref() != Some(&ids) {
+                    debug!(service = %service_name, count = ids.len(), "polled instances changed");
+                    last_ids = Some(ids);
+                    callback(instances);
+                }
+            }
+        })
+    }
+
+    /// Map `key` to one healthy instance of `service_name` via consistent hashing.
+    ///
+    /// The same key keeps landing on the same instance while the healthy set is stable, and
+    /// only a minimal share of keys moves when instances come or go — handy for cache
+    /// sharding or session affinity.
+    ///
+    /// Returns [`Error::NoAvailableInstance`] when no healthy instance exists.
+    pub async fn select_by_hash(&self, service_name: &str, key: &str) -> Result<ServiceInstance> {
+        let instances = self.get_healthy_instances(service_name).await?;
+        balance::pick_by_hash(&instances, key)
+            .cloned()
+            .ok_or_else(|| Error::NoAvailableInstance(service_name.to_string()))
+    }
+
+    /// Register the service instance and return a [`ServiceGuard`] that deregisters it on drop.
+    ///
+    /// Useful for the common "registered for the lifetime of the process" pattern, since the
+    /// guard also deregisters when unwinding from a panic.
+    pub async fn register_guard(&self) -> Result<ServiceGuard> {
+        self.register().await?;
+        Ok(ServiceGuard::new(self.clone()))
+    }
+
+    /// Register, wait for `shutdown` to resolve, then deregister.
+    ///
+    /// `shutdown` can be any future, e.g. a custom set of signals or a cancellation token.
+    pub async fn run_until<F>(&self, shutdown: F) -> Result<()>
+    where
+        F: Future<Output = ()>,
+    {
+        self.register().await?;
+        shutdown.await;
+        self.deregister().await
+    }
+
+    /// Register, block until `SIGINT`/`SIGTERM` (Ctrl+C on non-Unix), then deregister.
+    ///
+    /// See [`shutdown_signal`](crate::shutdown_signal) for the exact signal set; use
+    /// [`run_until`](Self::run_until) to wait on a different one.
+    #[cfg(feature = "signal")]
+    pub async fn run_until_signal(&self) -> Result<()> {
+        self.register().await?;
+        let waited = crate::signal::shutdown_signal().await;
+        // Deregister even if installing the signal handler failed.
+        self.deregister().await?;
+        waited
+    }
+
+    /// Deregister automatically when the process receives `SIGINT`/`SIGTERM` (Ctrl+C on
+    /// non-Unix), then exit with the conventional status for that signal: `130` for `SIGINT`,
+    /// `143` for `SIGTERM`.
+    ///
+    /// An atexit-style hook for scripts that do not want to wire up signal handling: it runs on
+    /// its own thread and runtime, so it works whether or not the caller uses tokio. The hook
+    /// skips deregistration if the instance is no longer registered, so calling
+    /// [`deregister`](Self::deregister) by hand beforehand is fine. Because the handlers replace
+    /// the default signal behavior, the hook always terminates the process once a signal
+    /// arrives. If listening for signals fails after installation, the failure is logged and
+    /// the process is left running and registered.
+    #[cfg(feature = "signal")]
+    pub fn on_shutdown(&self) -> Result<()> {
+        let manager = self.clone();
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+        std::thread::Builder::new()
+            .name("ez-discovery-shutdown".to_string())
+            .spawn(move || {
+                let rt = match tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                {
+                    Ok(rt) => rt,
+                    Err(err) => {
+                        let _ = ready_tx.send(Err(Error::Io(err)));
+                        return;
+                    }
+                };
+                rt.block_on(async move {
+                    let signal = match crate::signal::listen() {
+                        Ok(signal) => {
+                            let _ = ready_tx.send(Ok(()));
+                            signal
+                        }
+                        Err(err) => {
+                            let _ = ready_tx.send(Err(err));
+                            return;
+                        }
+                    };
+                    let received = match signal.await {
+                        Ok(received) => received,
+                        Err(err) => {
+                            warn!(error = %err, "waiting for shutdown signal failed");
+                            return;
+                        }
+                    };
+                    if manager.registered() {
+                        if let Err(err) = manager.deregister().await {
+                            warn!(
+                                service = %manager.inner.service_name,
+                                error = %err,
+                                "failed to deregister service on shutdown"
+                            );
+                        }
+                    }
+                    std::process::exit(received.exit_code());
+                });
+            })?;
+        ready_rx.recv().unwrap_or_else(|_| {
+            Err(Error::Io(io::Error::other(
+                "shutdown hook thread exited before installing signal handlers",
+            )))
+        })
+    }
+
+    /// Spawn a background task that every `interval` checks the instance is still present in
+    /// Nacos and re-registers it if it went missing.
+    ///
+    /// The task only acts while the manager is registered, so it never resurrects an instance
+    /// after [`deregister`](Self::deregister), and it exits once every clone of the manager is
+    /// dropped. Dropping the returned handle does **not** stop the task; for a deterministic
+    /// shutdown abort the handle and await it, then deregister:
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use ez_rust_discovery::ServiceManager;
+    /// # async fn demo(manager: ServiceManager) -> ez_rust_discovery::Result<()> {
+    /// let keepalive = manager.spawn_keepalive(Duration::from_secs(30));
+    /// // ... serve ...
+    /// keepalive.abort();
+    /// let _ = keepalive.await;
+    /// manager.deregister().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// To have [`register`](Self::register) manage the task instead, configure
+    /// [`keepalive`](crate::ServiceConfigBuilder::keepalive).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero or if called outside of a tokio runtime.
+    pub fn spawn_keepalive(&self, interval: Duration) -> JoinHandle<()> {
+        let inner = Arc::downgrade(&self.inner);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let Some(inner) = inner.upgrade() else {
+                    return;
+                };
+                let manager = ServiceManager { inner };
+                if let Err(err) = manager.ensure_registered().await {
+                    warn!(
+                        service = %manager.inner.service_name,
+                        error = %err,
+                        "keepalive check failed"
+                    );
+                }
+            }
+        })
+    }
+
     /// Blocking variant of [`register`](Self::register).
     ///
-    /// **Do not** call from within an existing tokio runtime — it will panic.
+    /// **Do not** call from within an existing tokio runtime — it returns an error instead of
+    /// nesting executors; `.await` [`register`](Self::register) there.
+    #[cfg(feature = "blocking")]
     pub fn register_blocking(&self) -> Result<()> {
         block_on(self.register())
     }
 
     /// Blocking variant of [`deregister`](Self::deregister).
     ///
-    /// **Do not** call from within an existing tokio runtime — it will panic.
+    /// **Do not** call from within an existing tokio runtime — it returns an error instead of
+    /// nesting executors; `.await` [`deregister`](Self::deregister) there.
+    #[cfg(feature = "blocking")]
     pub fn deregister_blocking(&self) -> Result<()> {
         block_on(self.deregister())
     }
@@ -146,36 +1176,1452 @@ impl ServiceManager {
         &self.inner.service_name
     }
 
-    /// Group of the registered service.
+    /// Group of the registered service, fixed when the configuration was built: register and
+    /// deregister always target it, even if [`set_default_group`](crate::set_default_group)
+    /// is called later.
     pub fn group(&self) -> &str {
         &self.inner.group
     }
 
+    /// Handle to the underlying nacos-sdk `NamingService`, for SDK calls this crate does not
+    /// wrap (batch operations, cluster-filtered queries, ...). `None` when the manager was
+    /// built on a non-Nacos [`Registry`] via [`with_registry`](Self::with_registry).
+    /// Managers built via [`with_naming_service`](Self::with_naming_service) return the
+    /// service they were given.
+    ///
+    /// The handle is cheap to clone and shares the manager's connection. Its API follows
+    /// `nacos-sdk` and may change whenever this crate bumps its `nacos-sdk` dependency.
+    /// Registrations made through it bypass the manager's bookkeeping.
+    pub fn naming_service(&self) -> Option<NamingService> {
+        self.inner.naming.clone()
+    }
+
+    /// Advertised host registered to Nacos.
+    pub fn service_host(&self) -> &str {
+        &self.inner.service_host
+    }
+
+    /// Advertised port registered to Nacos; pass the same value to the server's bind call so
+    /// the two cannot drift apart.
+    pub fn service_port(&self) -> u16 {
+        self.inner.service_port
+    }
+
+    /// Check that the server is really reachable at the advertised address, given the
+    /// address it actually bound, e.g. from `TcpListener::local_addr`.
+    ///
+    /// Fails with [`Error::InvalidConfig`] when the ports differ, or when the server listens
+    /// on a single IP other than the advertised one. Wildcard binds (`0.0.0.0`, `[::]`) and
+    /// advertised hostnames match any IP. Discovery-only managers always fail.
+    pub fn assert_bound(&self, actual: SocketAddr) -> Result<()> {
+        self.ensure_registrable("assert the bound address")?;
+        let (host, port) = (&self.inner.service_host, self.inner.service_port);
+        if actual.port() != port {
+            return Err(Error::invalid_config(format!(
+                "advertised port {port} but the server is bound to {actual}"
+            )));
+        }
+        let ip = actual.ip();
+        if !ip.is_unspecified()
+            && host
+                .parse::<IpAddr>()
+                .is_ok_and(|advertised| advertised != ip)
+        {
+            return Err(Error::invalid_config(format!(
+                "advertised host {host} but the server only listens on {actual}"
+            )));
+        }
+        Ok(())
+    }
+
     /// Read-only view of the registered instance (host, port, metadata, ...).
-    pub fn instance(&self) -> &ServiceInstance {
-        &self.inner.instance
+    ///
+    /// The view holds a read lock on the instance: keep it short-lived, and never hold it
+    /// across an `.await` or while calling a setter such as [`set_healthy`](Self::set_healthy),
+    /// which would deadlock. Use [`instance_snapshot`](Self::instance_snapshot) for an owned
+    /// copy.
+    pub fn instance(&self) -> impl Deref<Target = ServiceInstance> + '_ {
+        self.inner
+            .instance
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Owned copy of the registered instance, reflecting every update made so far.
+    pub fn instance_snapshot(&self) -> ServiceInstance {
+        self.instance().clone()
+    }
+
+    /// Ask the registry whether this manager's instance is currently listed and healthy.
+    ///
+    /// Unlike the local bookkeeping behind [`register`](Self::register), this reflects the
+    /// actual registry state, which suits readiness endpoints. A service the registry does not
+    /// know about, or a [discovery-only](crate::ServiceConfigBuilder::discovery_only) manager,
+    /// yields `false` rather than an error.
+    pub async fn is_registered(&self) -> Result<bool> {
+        if self.inner.discovery_only {
+            return Ok(false);
+        }
+        let own = self.instance_snapshot();
+        let instances = match self
+            .fetch_instances(&self.inner.service_name, &self.inner.group)
+            .await
+        {
+            Ok(instances) => instances,
+            Err(err) if err.is_not_found() => return Ok(false),
+            Err(err) => return Err(err),
+        };
+        Ok(instances
+            .iter()
+            .any(|i| same_endpoint(i, &own) && i.healthy))
+    }
+
+    /// Registration counters for alerting, e.g. on repeated register failures.
+    pub fn metrics(&self) -> &Metrics {
+        &self.inner.metrics
+    }
+
+    /// Whether the instance is ephemeral (kept alive by client heartbeats and dropped by Nacos
+    /// when they stop) rather than persistent. Reflects [`set_ephemeral`](Self::set_ephemeral).
+    pub fn is_ephemeral(&self) -> bool {
+        self.inner
+            .instance
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .ephemeral
+    }
+
+    /// Whether the manager was built [discovery-only](crate::ServiceConfigBuilder::discovery_only)
+    /// and therefore has no instance of its own to register.
+    pub fn is_discovery_only(&self) -> bool {
+        self.inner.discovery_only
+    }
+
+    /// Whether [`register`](Self::register) succeeded and has not been undone by
+    /// [`deregister`](Self::deregister) since.
+    pub(crate) fn registered(&self) -> bool {
+        self.inner.registered.load(Ordering::Acquire)
+    }
+
+    /// Re-register the instance if it is supposed to be registered but Nacos no longer lists it.
+    async fn ensure_registered(&self) -> Result<()> {
+        if !self.registered() {
+            return Ok(());
+        }
+        let instances = self
+            .fetch_instances(&self.inner.service_name, &self.inner.group)
+            .await?;
+        // Re-check under the lifecycle lock: a deregister may have completed while the query
+        // was in flight, and must not start while we re-register.
+        let _lifecycle = self.inner.lifecycle.lock().await;
+        let own = self.instance_snapshot();
+        if !self.registered() || instances.iter().any(|i| same_endpoint(i, &own)) {
+            return Ok(());
+        }
+        warn!(
+            service = %self.inner.service_name,
+            host = %own.ip,
+            port = own.port,
+            "instance missing from nacos, re-registering"
+        );
+        self.register_instance(&self.inner.service_name, own).await
+    }
+
+    /// Start the [`keepalive`](crate::ServiceConfigBuilder::keepalive) watchdog unless it is
+    /// disabled or already running, or the instance is persistent.
+    fn start_watchdog(&self) {
+        let Some(interval) = self.inner.keepalive else {
+            return;
+        };
+        if !self.is_ephemeral() {
+            // Nacos keeps persistent instances until deregistered and health-checks them
+            // server-side, so there is nothing for the client to restore. `build` already
+            // warned about the combination.
+            return;
+        }
+        let mut watchdog = self
+            .inner
+            .watchdog
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if watchdog.as_ref().is_some_and(|task| !task.is_finished()) {
+            return;
+        }
+        *watchdog = Some(self.spawn_keepalive(interval));
+        debug!(service = %self.inner.service_name, ?interval, "keepalive watchdog started");
+    }
+
+    /// Abort the keepalive watchdog, if running.
+    fn stop_watchdog(&self) {
+        let watchdog = self
+            .inner
+            .watchdog
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(watchdog) = watchdog {
+            watchdog.abort();
+        }
+    }
+
+    /// Sleep for the configured register jitter, once per manager.
+    async fn startup_jitter(&self) {
+        let Some(max) = self.inner.register_jitter else {
+            return;
+        };
+        if self.inner.jittered.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let delay = jitter_delay(max, rand::random());
+        debug!(service = %self.inner.service_name, ?delay, "delaying first registration");
+        tokio::time::sleep(delay).await;
+    }
+
+    /// Reject `operation` on the primary instance of a discovery-only manager.
+    fn ensure_registrable(&self, operation: &str) -> Result<()> {
+        if self.inner.discovery_only {
+            return Err(Error::invalid_config(format!(
+                "cannot {operation}: the manager is discovery-only; set `service_name` and \
+                 `service_port` instead of `discovery_only` to register an instance"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Apply `update` to the stored instance and, if registered, push it to Nacos.
+    async fn update_instance(&self, update: impl FnOnce(&mut ServiceInstance)) -> Result<()> {
+        let _lifecycle = self.inner.lifecycle.lock().await;
+        let instance = self.set_instance(update);
+        if self.registered() {
+            self.register_instance(&self.inner.service_name, instance)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Apply `update` to the stored instance and return the result.
+    fn set_instance(&self, update: impl FnOnce(&mut ServiceInstance)) -> ServiceInstance {
+        let mut guard = self
+            .inner
+            .instance
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        update(&mut guard);
+        guard.clone()
+    }
+
+    /// Run `call` for the primary group and then each extra group. A failing group does not
+    /// stop the rest; failures are logged and the first one is returned.
+    async fn in_all_groups<'a, F, Fut>(&'a self, operation: &'static str, call: F) -> Result<()>
+    where
+        F: Fn(&'a str) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        if self.inner.extra_groups.is_empty() {
+            return call(&self.inner.group).await;
+        }
+        let mut first_err = None;
+        for group in std::iter::once(&self.inner.group).chain(&self.inner.extra_groups) {
+            if let Err(err) = call(group).await {
+                warn!(group = %group, error = %err, "{operation} failed in group");
+                first_err.get_or_insert(err);
+            }
+        }
+        first_err.map_or(Ok(()), Err)
+    }
+
+    /// Await `future`, bounded by the configured timeout if there is one.
+    async fn with_timeout<T>(
+        &self,
+        operation: &'static str,
+        future: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        bounded(self.inner.timeout, operation, future).await
+    }
+}
+
+/// Prints a one-line summary: group, service name, host, port, weight and metadata.
+impl std::fmt::Display for ServiceManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&summary(
+            &self.inner.service_name,
+            &self.inner.group,
+            &self.instance_snapshot(),
+        ))
     }
 }
 
 impl std::fmt::Debug for ServiceManager {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let instance = self.instance_snapshot();
         f.debug_struct("ServiceManager")
             .field("service_name", &self.inner.service_name)
             .field("group", &self.inner.group)
-            .field("ip", &self.inner.instance.ip)
-            .field("port", &self.inner.instance.port)
+            .field("ip", &instance.ip)
+            .field("port", &instance.port)
             .finish()
     }
 }
 
+/// Await `future`, failing with [`Error::Timeout`] if it outlasts `timeout`.
+async fn bounded<T>(
+    timeout: Option<Duration>,
+    operation: &'static str,
+    future: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let Some(timeout) = timeout else {
+        return future.await;
+    };
+    tokio::time::timeout(timeout, future)
+        .await
+        .map_err(|_| Error::Timeout { operation, timeout })?
+}
+
+/// Identity of an instance for change detection: its id, or `ip#port` when it has none.
+fn instance_key(instance: &ServiceInstance) -> String {
+    instance
+        .instance_id
+        .clone()
+        .unwrap_or_else(|| format!("{}#{}", instance.ip, instance.port))
+}
+
+/// A random part of `max`, given a `jitter` in `[0, 1]`.
+fn jitter_delay(max: Duration, jitter: f64) -> Duration {
+    max.mul_f64(jitter.clamp(0.0, 1.0))
+}
+
+/// Delay before retry number `attempt` (1-based): `base * 2^(attempt - 1)`, scaled into
+/// `[0.5, 1.0)` of that value by the uniform `jitter` sample in `[0, 1)`.
+fn backoff_delay(base: Duration, attempt: u32, jitter: f64) -> Duration {
+    let exp = base.saturating_mul(
+        1u32.checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u32::MAX),
+    );
+    exp.mul_f64(0.5 + jitter.clamp(0.0, 1.0) / 2.0)
+}
+
+/// One-line summary of a registration, with metadata sorted by key for stable output.
+fn summary(service_name: &str, group: &str, instance: &ServiceInstance) -> String {
+    let mut metadata: Vec<_> = instance.metadata.iter().collect();
+    metadata.sort();
+    let metadata = metadata
+        .into_iter()
+        .map(|(k, v)| format!("{k}={v}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "{group}@@{service_name} {}:{} weight={} metadata={{{metadata}}}",
+        instance.ip, instance.port, instance.weight
+    )
+}
+
+/// Whether two instances point at the same `ip:port`.
+fn same_endpoint(a: &ServiceInstance, b: &ServiceInstance) -> bool {
+    a.ip == b.ip && a.port == b.port
+}
+
+/// Whether `instance` carries every key/value pair of `filters` in its metadata.
+fn metadata_matches(instance: &ServiceInstance, filters: &HashMap<String, String>) -> bool {
+    filters
+        .iter()
+        .all(|(k, v)| instance.metadata.get(k) == Some(v))
+}
+
+/// Keep only instances that are healthy and enabled.
+fn healthy_only(instances: Vec<ServiceInstance>) -> Vec<ServiceInstance> {
+    instances
+        .into_iter()
+        .filter(|i| i.healthy && i.enabled)
+        .collect()
+}
+
 /// Block on a future using an ad-hoc current-thread tokio runtime.
-fn block_on<F, T>(fut: F) -> Result<T>
+///
+/// Refuses to run inside an existing tokio runtime, where starting a nested one would panic.
+pub(crate) fn block_on<F, T>(fut: F) -> Result<T>
 where
     F: Future<Output = Result<T>>,
 {
+    if tokio::runtime::Handle::try_current().is_ok() {
+        return Err(Error::Io(io::Error::other(
+            "blocking API called from within a tokio runtime; use the async variant instead",
+        )));
+    }
     let rt = tokio::runtime::Builder::new_current_thread()
         .enable_all()
         .build()
         .map_err(Error::Io)?;
     rt.block_on(fut)
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, clippy::expect_used)]
+mod tests {
+    use super::*;
+    use crate::config::DEFAULT_GROUP;
+    use crate::mock::MockRegistry;
+
+    #[tokio::test]
+    async fn block_on_inside_runtime_errors_instead_of_panicking() {
+        let err = block_on(async { Ok(()) }).unwrap_err();
+        assert!(matches!(err, Error::Io(_)));
+    }
+
+    fn instance(port: i32, healthy: bool, enabled: bool) -> ServiceInstance {
+        ServiceInstance {
+            ip: "10.0.0.1".to_string(),
+            port,
+            healthy,
+            enabled,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn healthy_only_drops_unhealthy_and_disabled() {
+        let instances = vec![
+            instance(1, true, true),
+            instance(2, false, true),
+            instance(3, true, false),
+            instance(4, false, false),
+            instance(5, true, true),
+        ];
+        let ports: Vec<i32> = healthy_only(instances).iter().map(|i| i.port).collect();
+        assert_eq!(ports, vec![1, 5]);
+    }
+
+    #[test]
+    fn summary_is_single_line_and_sorted() {
+        let mut inst = instance(9000, true, true);
+        inst.weight = 2.0;
+        inst.metadata.insert("zone".to_string(), "a".to_string());
+        inst.metadata
+            .insert("gRPC_port".to_string(), "9000".to_string());
+        assert_eq!(
+            summary("svc", "DEFAULT_GROUP", &inst),
+            "DEFAULT_GROUP@@svc 10.0.0.1:9000 weight=2 metadata={gRPC_port=9000, zone=a}"
+        );
+    }
+
+    #[test]
+    fn metadata_matches_requires_every_pair() {
+        let mut inst = instance(9000, true, true);
+        inst.metadata.insert("version".to_string(), "2".to_string());
+        inst.metadata.insert("zone".to_string(), "a".to_string());
+        let filters = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        assert!(metadata_matches(&inst, &filters(&[])));
+        assert!(metadata_matches(&inst, &filters(&[("version", "2")])));
+        assert!(metadata_matches(
+            &inst,
+            &filters(&[("version", "2"), ("zone", "a")])
+        ));
+        assert!(!metadata_matches(
+            &inst,
+            &filters(&[("version", "2"), ("zone", "b")])
+        ));
+        assert!(!metadata_matches(&inst, &filters(&[("region", "cn")])));
+    }
+
+    #[test]
+    fn same_endpoint_compares_ip_and_port() {
+        let a = instance(9000, true, true);
+        let mut b = instance(9000, false, false);
+        assert!(same_endpoint(&a, &b));
+        b.port = 9001;
+        assert!(!same_endpoint(&a, &b));
+    }
+
+    #[test]
+    fn backoff_delay_grows_exponentially_with_jitter() {
+        let base = Duration::from_millis(100);
+        assert_eq!(backoff_delay(base, 1, 0.0), Duration::from_millis(50));
+        assert_eq!(backoff_delay(base, 1, 1.0), Duration::from_millis(100));
+        assert_eq!(backoff_delay(base, 3, 0.0), Duration::from_millis(200));
+        assert_eq!(backoff_delay(base, 3, 0.5), Duration::from_millis(300));
+        // Huge attempt counts saturate instead of overflowing.
+        assert!(backoff_delay(base, 64, 0.0) > Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn jitter_delay_stays_within_max() {
+        let max = Duration::from_millis(200);
+        assert_eq!(jitter_delay(max, 0.0), Duration::ZERO);
+        assert_eq!(jitter_delay(max, 0.5), Duration::from_millis(100));
+        assert_eq!(jitter_delay(max, 1.5), max);
+    }
+
+    #[tokio::test]
+    async fn register_jitter_applies_to_the_first_registration_only() {
+        let config = test_config()
+            .register_jitter(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        let manager = ServiceManager::with_registry(config, MockRegistry::new()).unwrap();
+        manager.register().await.unwrap();
+        assert!(manager.inner.jittered.load(Ordering::Acquire));
+
+        let started = Instant::now();
+        manager.register().await.unwrap();
+        assert!(started.elapsed() < Duration::from_millis(50));
+    }
+
+    #[test]
+    fn block_on_outside_runtime_runs_future() {
+        assert_eq!(block_on(async { Ok(42) }).unwrap(), 42);
+    }
+
+    fn test_config() -> crate::ServiceConfigBuilder {
+        ServiceConfig::builder()
+            .nacos_addr("127.0.0.1:8848")
+            .namespace("public")
+            .service_name("orders")
+            .service_host("10.0.0.9")
+            .service_port(8080)
+    }
+
+    fn mock_manager() -> (ServiceManager, MockRegistry) {
+        let config = test_config().build().unwrap();
+        let mock = MockRegistry::new();
+        let manager = ServiceManager::with_registry(config, mock.clone()).unwrap();
+        (manager, mock)
+    }
+
+    #[tokio::test]
+    async fn register_and_deregister_go_through_registry() {
+        let (manager, mock) = mock_manager();
+        manager.register().await.unwrap();
+        let registered = mock.registered_instances("orders", DEFAULT_GROUP);
+        assert_eq!(registered.len(), 1);
+        assert_eq!(registered[0].ip, "10.0.0.9");
+        assert_eq!(registered[0].port, 8080);
+
+        manager.deregister().await.unwrap();
+        assert!(
+            mock.registered_instances("orders", DEFAULT_GROUP)
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn is_registered_reflects_registry_state() {
+        let (manager, mock) = mock_manager();
+        assert!(!manager.is_registered().await.unwrap());
+
+        manager.register().await.unwrap();
+        assert!(manager.is_registered().await.unwrap());
+
+        manager.set_healthy(false).await.unwrap();
+        assert!(!manager.is_registered().await.unwrap());
+
+        mock.push_instances("orders", DEFAULT_GROUP, Vec::new());
+        assert!(!manager.is_registered().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn instance_id_reaches_registered_instance() {
+        let config = test_config().instance_id("orders-0").build().unwrap();
+        let mock = MockRegistry::new();
+        let manager = ServiceManager::with_registry(config, mock.clone()).unwrap();
+        manager.register().await.unwrap();
+        let registered = mock.registered_instances("orders", DEFAULT_GROUP);
+        assert_eq!(registered[0].instance_id.as_deref(), Some("orders-0"));
+    }
+
+    #[tokio::test]
+    async fn extra_groups_register_and_deregister_everywhere() {
+        let config = test_config().extra_groups(["SHARED"]).build().unwrap();
+        let mock = MockRegistry::new();
+        let manager = ServiceManager::with_registry(config, mock.clone()).unwrap();
+
+        manager.register().await.unwrap();
+        assert_eq!(mock.registered_instances("orders", DEFAULT_GROUP).len(), 1);
+        assert_eq!(mock.registered_instances("orders", "SHARED").len(), 1);
+
+        manager.deregister().await.unwrap();
+        assert!(
+            mock.registered_instances("orders", DEFAULT_GROUP)
+                .is_empty()
+        );
+        assert!(mock.registered_instances("orders", "SHARED").is_empty());
+    }
+
+    /// Registry that rejects registrations in one group and delegates everything else.
+    struct GroupRejectingRegistry(MockRegistry, &'static str);
+
+    #[async_trait::async_trait]
+    impl Registry for GroupRejectingRegistry {
+        async fn register(
+            &self,
+            service_name: &str,
+            group: &str,
+            instance: ServiceInstance,
+        ) -> Result<()> {
+            if group == self.1 {
+                return Err(Error::Unsupported(format!("group `{group}`")));
+            }
+            self.0.register(service_name, group, instance).await
+        }
+
+        async fn deregister(
+            &self,
+            service_name: &str,
+            group: &str,
+            instance: ServiceInstance,
+        ) -> Result<()> {
+            self.0.deregister(service_name, group, instance).await
+        }
+
+        async fn get_instances(
+            &self,
+            service_name: &str,
+            group: &str,
+        ) -> Result<Vec<ServiceInstance>> {
+            self.0.get_instances(service_name, group).await
+        }
+    }
+
+    #[tokio::test]
+    async fn failing_group_does_not_skip_the_rest() {
+        let config = test_config().extra_groups(["B", "C"]).build().unwrap();
+        let mock = MockRegistry::new();
+        let manager =
+            ServiceManager::with_registry(config, GroupRejectingRegistry(mock.clone(), "B"))
+                .unwrap();
+
+        let err = manager.register().await.unwrap_err();
+        assert!(matches!(err, Error::Unsupported(ref m) if m.contains('B')));
+        assert!(!manager.registered());
+        assert_eq!(mock.registered_instances("orders", DEFAULT_GROUP).len(), 1);
+        assert_eq!(mock.registered_instances("orders", "C").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn register_batch_covers_every_group() {
+        let config = test_config().extra_groups(["B", "C"]).build().unwrap();
+        let mock = MockRegistry::new();
+        let manager =
+            ServiceManager::with_registry(config, GroupRejectingRegistry(mock.clone(), "B"))
+                .unwrap();
+
+        let batch = vec![("billing".to_string(), manager.instance_snapshot())];
+        let err = manager.register_batch(batch).await.unwrap_err();
+        assert!(matches!(err, Error::Unsupported(ref m) if m.contains('B')));
+        assert_eq!(mock.registered_instances("billing", DEFAULT_GROUP).len(), 1);
+        assert_eq!(mock.registered_instances("billing", "C").len(), 1);
+        assert_eq!(manager.metrics().register_failures(), 1);
+    }
+
+    /// Registry that assigns an id on registration, like the Nacos server does.
+    struct IdAssigningRegistry(MockRegistry);
+
+    #[async_trait::async_trait]
+    impl Registry for IdAssigningRegistry {
+        async fn register(
+            &self,
+            service_name: &str,
+            group: &str,
+            mut instance: ServiceInstance,
+        ) -> Result<()> {
+            instance.instance_id = Some(format!("{}#{}", instance.ip, instance.port));
+            self.0.register(service_name, group, instance).await
+        }
+
+        async fn deregister(
+            &self,
+            service_name: &str,
+            group: &str,
+            instance: ServiceInstance,
+        ) -> Result<()> {
+            self.0.deregister(service_name, group, instance).await
+        }
+
+        async fn get_instances(
+            &self,
+            service_name: &str,
+            group: &str,
+        ) -> Result<Vec<ServiceInstance>> {
+            self.0.get_instances(service_name, group).await
+        }
+    }
+
+    #[tokio::test]
+    async fn register_detailed_reports_registration() {
+        let (manager, _mock) = mock_manager();
+        let registration = manager.register_detailed().await.unwrap();
+        assert_eq!(registration.instance_id, None);
+        assert_eq!(
+            registration.to_string(),
+            "DEFAULT_GROUP@@orders 10.0.0.9:8080"
+        );
+
+        let config = test_config().build().unwrap();
+        let registry = IdAssigningRegistry(MockRegistry::new());
+        let manager = ServiceManager::with_registry(config, registry).unwrap();
+        let registration = manager.register_detailed().await.unwrap();
+        assert_eq!(registration.instance_id.as_deref(), Some("10.0.0.9#8080"));
+        assert_eq!(
+            (registration.host.as_str(), registration.port),
+            ("10.0.0.9", 8080)
+        );
+    }
+
+    #[tokio::test]
+    async fn zone_reaches_registered_instance() {
+        let config = test_config().zone("az-2").build().unwrap();
+        let mock = MockRegistry::new();
+        let manager = ServiceManager::with_registry(config, mock.clone()).unwrap();
+        manager.register().await.unwrap();
+        let registered = mock.registered_instances("orders", DEFAULT_GROUP);
+        assert_eq!(registered[0].cluster_name.as_deref(), Some("az-2"));
+        assert_eq!(
+            registered[0]
+                .metadata
+                .get(crate::config::META_ZONE)
+                .map(String::as_str),
+            Some("az-2")
+        );
+    }
+
+    #[tokio::test]
+    async fn list_services_paginates_group() {
+        let (manager, mock) = mock_manager();
+        for name in ["c", "a", "b"] {
+            mock.push_instances(name, DEFAULT_GROUP, vec![instance(1, true, true)]);
+        }
+        mock.push_instances("other", "OTHER_GROUP", vec![instance(1, true, true)]);
+
+        let (page, total) = manager.list_services(None, 1, 2).await.unwrap();
+        assert_eq!((page, total), (vec!["a".to_string(), "b".to_string()], 3));
+        let (page, _) = manager.list_services(None, 2, 2).await.unwrap();
+        assert_eq!(page, vec!["c".to_string()]);
+        let (page, total) = manager
+            .list_services(Some("OTHER_GROUP".to_string()), 1, 10)
+            .await
+            .unwrap();
+        assert_eq!((page, total), (vec!["other".to_string()], 1));
+
+        let err = manager.list_services(None, 0, 10).await.unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(_)));
+    }
+
+    #[tokio::test]
+    async fn guard_skips_deregister_after_manual_deregister() {
+        let (manager, mock) = mock_manager();
+        let guard = manager.register_guard().await.unwrap();
+        manager.deregister().await.unwrap();
+        drop(guard);
+        assert_eq!(
+            mock.deregistered_instances("orders", DEFAULT_GROUP).len(),
+            1
+        );
+
+        let guard = manager.register_guard().await.unwrap();
+        drop(guard);
+        // The deregistration was spawned onto this runtime; let it run.
+        tokio::task::yield_now().await;
+        assert_eq!(
+            mock.deregistered_instances("orders", DEFAULT_GROUP).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn guard_outside_runtime_deregisters_before_drop_returns() {
+        let (manager, mock) = mock_manager();
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let guard = rt.block_on(manager.register_guard()).unwrap();
+        drop(guard);
+        assert_eq!(
+            mock.deregistered_instances("orders", DEFAULT_GROUP).len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn register_batch_groups_by_service() {
+        let (manager, mock) = mock_manager();
+        manager
+            .register_batch(vec![
+                ("users".to_string(), instance(1, true, true)),
+                ("billing".to_string(), instance(2, true, true)),
+                ("users".to_string(), instance(3, true, true)),
+            ])
+            .await
+            .unwrap();
+        let ports = |service| {
+            let mut ports: Vec<i32> = mock
+                .registered_instances(service, DEFAULT_GROUP)
+                .iter()
+                .map(|i| i.port)
+                .collect();
+            ports.sort_unstable();
+            ports
+        };
+        assert_eq!(ports("users"), vec![1, 3]);
+        assert_eq!(ports("billing"), vec![2]);
+    }
+
+    #[tokio::test]
+    async fn set_healthy_pushes_only_while_registered() {
+        let (manager, mock) = mock_manager();
+        manager.set_healthy(false).await.unwrap();
+        assert!(
+            mock.registered_instances("orders", DEFAULT_GROUP)
+                .is_empty()
+        );
+
+        manager.register().await.unwrap();
+        manager.set_healthy(false).await.unwrap();
+        let registered = mock.registered_instances("orders", DEFAULT_GROUP);
+        assert_eq!(registered.len(), 1);
+        assert!(!registered[0].healthy);
+    }
+
+    #[tokio::test]
+    async fn disabled_instance_is_registered_but_not_selected() {
+        let config = test_config().enabled(false).build().unwrap();
+        let mock = MockRegistry::new();
+        let manager = ServiceManager::with_registry(config, mock.clone()).unwrap();
+        manager.register().await.unwrap();
+        let registered = mock.registered_instances("orders", DEFAULT_GROUP);
+        assert_eq!(registered.len(), 1);
+        assert!(!registered[0].enabled);
+        assert!(manager.select_instance("orders").await.is_err());
+
+        manager.set_enabled(true).await.unwrap();
+        assert!(mock.registered_instances("orders", DEFAULT_GROUP)[0].enabled);
+        assert_eq!(manager.select_instance("orders").await.unwrap().port, 8080);
+    }
+
+    #[tokio::test]
+    async fn set_weight_validates_and_pushes() {
+        let (manager, mock) = mock_manager();
+        manager.register().await.unwrap();
+        manager.set_weight(0.25).await.unwrap();
+        assert_eq!(
+            mock.registered_instances("orders", DEFAULT_GROUP)[0].weight,
+            0.25
+        );
+
+        let err = manager.set_weight(f64::NAN).await.unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(ref m) if m.contains("weight")));
+        assert_eq!(manager.instance().weight, 0.25);
+    }
+
+    #[tokio::test]
+    async fn clones_share_instance_state() {
+        let (manager, _mock) = mock_manager();
+        let clone = manager.clone();
+        tokio::spawn(async move {
+            clone.set_weight(3.0).await.unwrap();
+            clone.set_healthy(false).await.unwrap();
+        })
+        .await
+        .unwrap();
+        let instance = manager.instance();
+        assert_eq!(instance.weight, 3.0);
+        assert!(!instance.healthy);
+    }
+
+    #[tokio::test]
+    async fn update_metadata_is_visible_to_clients() {
+        let (manager, _mock) = mock_manager();
+        manager.register().await.unwrap();
+        manager
+            .update_metadata("draining", Some("true".to_string()))
+            .await
+            .unwrap();
+        let filters = HashMap::from([("draining".to_string(), "true".to_string())]);
+        let seen = manager
+            .get_instances_by_meta("orders", &filters)
+            .await
+            .unwrap();
+        assert_eq!(seen.len(), 1);
+
+        manager.update_metadata("draining", None).await.unwrap();
+        let seen = manager.get_instances("orders", None).await.unwrap();
+        assert!(!seen[0].metadata.contains_key("draining"));
+
+        let err = manager
+            .update_metadata(crate::META_PROTOCOL, Some("tcp".to_string()))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(_)));
+        assert!(manager.update_metadata(" ", None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn set_ephemeral_re_registers_under_new_kind() {
+        let (manager, mock) = mock_manager();
+        manager.set_ephemeral(true).await.unwrap();
+        manager.register().await.unwrap();
+        assert!(manager.is_ephemeral());
+        manager.set_ephemeral(false).await.unwrap();
+        assert!(!manager.is_ephemeral());
+
+        let deregistered = mock.deregistered_instances("orders", DEFAULT_GROUP);
+        assert_eq!(deregistered.len(), 1);
+        assert!(deregistered[0].ephemeral);
+        let registered = mock.registered_instances("orders", DEFAULT_GROUP);
+        assert_eq!(registered.len(), 1);
+        assert!(!registered[0].ephemeral);
+        assert!(manager.registered());
+    }
+
+    /// Registry whose registrations take a while, to widen race windows.
+    struct SlowRegisterRegistry(MockRegistry);
+
+    #[async_trait::async_trait]
+    impl Registry for SlowRegisterRegistry {
+        async fn register(
+            &self,
+            service_name: &str,
+            group: &str,
+            instance: ServiceInstance,
+        ) -> Result<()> {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            self.0.register(service_name, group, instance).await
+        }
+
+        async fn deregister(
+            &self,
+            service_name: &str,
+            group: &str,
+            instance: ServiceInstance,
+        ) -> Result<()> {
+            self.0.deregister(service_name, group, instance).await
+        }
+
+        async fn get_instances(
+            &self,
+            service_name: &str,
+            group: &str,
+        ) -> Result<Vec<ServiceInstance>> {
+            self.0.get_instances(service_name, group).await
+        }
+    }
+
+    #[tokio::test]
+    async fn setter_racing_with_deregister_does_not_re_register() {
+        let mock = MockRegistry::new();
+        let config = test_config().build().unwrap();
+        let manager =
+            ServiceManager::with_registry(config, SlowRegisterRegistry(mock.clone())).unwrap();
+        manager.register().await.unwrap();
+
+        // The setter sees the instance registered and starts pushing the update; the
+        // deregister must not finish before that push lands.
+        let clone = manager.clone();
+        let update = tokio::spawn(async move { clone.set_weight(2.0).await });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        manager.deregister().await.unwrap();
+        update.await.unwrap().unwrap();
+
+        assert!(!manager.registered());
+        assert!(
+            mock.registered_instances("orders", DEFAULT_GROUP)
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn drain_disables_before_deregistering() {
+        let (manager, mock) = mock_manager();
+        manager.register().await.unwrap();
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        manager
+            .subscribe("orders", move |instances| {
+                sink.lock()
+                    .unwrap()
+                    .push(instances.iter().map(|i| i.enabled).collect::<Vec<_>>());
+            })
+            .await
+            .unwrap();
+
+        manager.drain(Duration::from_millis(10)).await.unwrap();
+        assert_eq!(*seen.lock().unwrap(), vec![vec![false], vec![]]);
+        assert!(!mock.deregistered_instances("orders", DEFAULT_GROUP)[0].enabled);
+        assert!(!manager.registered());
+        assert!(manager.instance().enabled);
+    }
+
+    #[tokio::test]
+    async fn select_instance_rotates_over_healthy_instances() {
+        let (manager, mock) = mock_manager();
+        mock.push_instances(
+            "users",
+            DEFAULT_GROUP,
+            vec![
+                instance(1, true, true),
+                instance(2, false, true),
+                instance(3, true, true),
+            ],
+        );
+        let mut ports = Vec::new();
+        for _ in 0..4 {
+            ports.push(manager.select_instance("users").await.unwrap().port);
+        }
+        ports.sort_unstable();
+        assert_eq!(ports, vec![1, 1, 3, 3]);
+
+        let err = manager.select_instance("missing").await.unwrap_err();
+        assert!(matches!(err, Error::NoAvailableInstance(name) if name == "missing"));
+    }
+
+    /// Always picks the instance with the highest port.
+    struct HighestPort;
+
+    impl LoadBalancer for HighestPort {
+        fn choose<'a>(&self, instances: &'a [ServiceInstance]) -> Option<&'a ServiceInstance> {
+            instances.iter().max_by_key(|i| i.port)
+        }
+    }
+
+    #[tokio::test]
+    async fn select_with_uses_custom_balancer() {
+        let (manager, mock) = mock_manager();
+        mock.push_instances(
+            "users",
+            DEFAULT_GROUP,
+            vec![
+                instance(1, true, true),
+                instance(3, false, true),
+                instance(2, true, true),
+            ],
+        );
+        let picked = manager.select_with("users", &HighestPort).await.unwrap();
+        assert_eq!(picked.port, 2);
+        let err = manager
+            .select_with("missing", &crate::RoundRobinBalancer::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::NoAvailableInstance(_)));
+    }
+
+    #[tokio::test]
+    async fn every_selector_reports_an_empty_pool() {
+        let (manager, mock) = mock_manager();
+        mock.push_instances("users", DEFAULT_GROUP, vec![instance(1, false, true)]);
+        let results = [
+            manager.select_instance("users").await,
+            manager.select_weighted("users").await,
+            manager.select_smooth_weighted("users").await,
+            manager.select_by_hash("users", "key").await,
+            manager.select_instance_excluding_self("users").await,
+        ];
+        for result in results {
+            let err = result.unwrap_err();
+            assert!(matches!(err, Error::NoAvailableInstance(ref name) if name == "users"));
+            assert!(!err.is_transient());
+        }
+    }
+
+    #[tokio::test]
+    async fn select_instance_excluding_self_skips_own_endpoint() {
+        let (manager, mock) = mock_manager();
+        manager.register().await.unwrap();
+        let mut peer = instance(8080, true, true);
+        peer.ip = "10.0.0.10".to_string();
+        mock.register("orders", DEFAULT_GROUP, peer).await.unwrap();
+
+        for _ in 0..3 {
+            let picked = manager
+                .select_instance_excluding_self("orders")
+                .await
+                .unwrap();
+            assert_eq!(picked.ip, "10.0.0.10");
+        }
+
+        mock.push_instances("orders", DEFAULT_GROUP, vec![manager.instance_snapshot()]);
+        let err = manager
+            .select_instance_excluding_self("orders")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::NoAvailableInstance(_)));
+    }
+
+    #[tokio::test]
+    async fn subscribe_receives_updates_until_unsubscribed() {
+        let (manager, mock) = mock_manager();
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        manager
+            .subscribe("users", move |instances| {
+                sink.lock().unwrap().push(instances.len());
+            })
+            .await
+            .unwrap();
+
+        mock.push_instances("users", DEFAULT_GROUP, vec![instance(1, true, true)]);
+        manager.unsubscribe("users").await.unwrap();
+        mock.push_instances("users", DEFAULT_GROUP, Vec::new());
+
+        assert_eq!(*seen.lock().unwrap(), vec![1]);
+    }
+
+    #[tokio::test]
+    async fn discovery_only_manager_discovers_but_refuses_to_register() {
+        let config = ServiceConfig::builder()
+            .nacos_addr("127.0.0.1:8848")
+            .namespace("public")
+            .discovery_only()
+            .build()
+            .unwrap();
+        let mock = MockRegistry::new();
+        let manager = ServiceManager::with_registry(config, mock.clone()).unwrap();
+        assert!(manager.is_discovery_only());
+
+        mock.push_instances("users", DEFAULT_GROUP, vec![instance(1, true, true)]);
+        assert_eq!(manager.select_instance("users").await.unwrap().port, 1);
+
+        let err = manager.register().await.unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(ref m) if m.contains("discovery-only")));
+        assert!(manager.register_guard().await.is_err());
+        assert!(manager.deregister().await.is_err());
+        assert!(mock.registered_instances("", DEFAULT_GROUP).is_empty());
+    }
+
+    #[tokio::test]
+    async fn keepalive_restores_instance_and_stops_when_aborted() {
+        let (manager, mock) = mock_manager();
+        let keepalive = manager.spawn_keepalive(Duration::from_millis(10));
+        manager.register().await.unwrap();
+
+        mock.push_instances("orders", DEFAULT_GROUP, Vec::new());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(mock.registered_instances("orders", DEFAULT_GROUP).len(), 1);
+
+        keepalive.abort();
+        assert!(keepalive.await.unwrap_err().is_cancelled());
+        manager.deregister().await.unwrap();
+        assert!(!manager.registered());
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(
+            mock.registered_instances("orders", DEFAULT_GROUP)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn assert_bound_compares_host_and_port() {
+        let (manager, _) = mock_manager();
+        let bound = |addr: &str| manager.assert_bound(addr.parse().unwrap());
+        bound("10.0.0.9:8080").unwrap();
+        bound("0.0.0.0:8080").unwrap();
+        bound("[::]:8080").unwrap();
+        let err = bound("0.0.0.0:9090").unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(ref m) if m.contains("advertised port 8080")));
+        let err = bound("127.0.0.1:8080").unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(ref m) if m.contains("10.0.0.9")));
+
+        let config = test_config()
+            .service_host("orders.internal")
+            .build()
+            .unwrap();
+        let manager = ServiceManager::with_registry(config, MockRegistry::new()).unwrap();
+        manager
+            .assert_bound("127.0.0.1:8080".parse().unwrap())
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn deregister_instance_removes_a_previous_processes_instance() {
+        let mock = MockRegistry::new();
+        let crashed =
+            ServiceManager::with_registry(test_config().build().unwrap(), mock.clone()).unwrap();
+        crashed.register().await.unwrap();
+        drop(crashed);
+
+        let config = test_config().service_port(8081).build().unwrap();
+        let restarted = ServiceManager::with_registry(config, mock.clone()).unwrap();
+        let stale = ServiceInstance {
+            ip: "10.0.0.9".to_string(),
+            port: 8080,
+            ..Default::default()
+        };
+        restarted
+            .deregister_instance("orders", stale.clone())
+            .await
+            .unwrap();
+        assert!(
+            mock.registered_instances("orders", DEFAULT_GROUP)
+                .is_empty()
+        );
+        restarted
+            .deregister_instance("orders", stale)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn keepalive_only_runs_for_ephemeral_instances() {
+        let config = test_config()
+            .ephemeral(false)
+            .keepalive(Duration::from_secs(60))
+            .build()
+            .unwrap();
+        let manager = ServiceManager::with_registry(config, MockRegistry::new()).unwrap();
+        manager.register().await.unwrap();
+        assert!(manager.inner.watchdog.lock().unwrap().is_none());
+
+        manager.set_ephemeral(true).await.unwrap();
+        assert!(manager.inner.watchdog.lock().unwrap().is_some());
+        manager.set_ephemeral(false).await.unwrap();
+        assert!(manager.inner.watchdog.lock().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn configured_keepalive_follows_register_and_deregister() {
+        let config = test_config()
+            .keepalive(Duration::from_millis(10))
+            .build()
+            .unwrap();
+        let mock = MockRegistry::new();
+        let manager = ServiceManager::with_registry(config, mock.clone()).unwrap();
+        manager.register().await.unwrap();
+
+        mock.push_instances("orders", DEFAULT_GROUP, Vec::new());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(mock.registered_instances("orders", DEFAULT_GROUP).len(), 1);
+
+        manager.deregister().await.unwrap();
+        assert!(manager.inner.watchdog.lock().unwrap().is_none());
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(
+            mock.registered_instances("orders", DEFAULT_GROUP)
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn metrics_count_registrations() {
+        let (manager, _mock) = mock_manager();
+        manager.register().await.unwrap();
+        manager.set_weight(2.0).await.unwrap();
+        let metrics = manager.metrics();
+        assert_eq!(metrics.registrations(), 2);
+        assert_eq!(metrics.registered_instances(), 1);
+
+        manager.deregister().await.unwrap();
+        assert_eq!(metrics.deregistrations(), 1);
+        assert_eq!(metrics.registered_instances(), 0);
+        assert_eq!(metrics.register_failures(), 0);
+
+        let config = test_config()
+            .timeout(Duration::from_millis(20))
+            .build()
+            .unwrap();
+        let manager = ServiceManager::with_registry(config, WedgedRegistry).unwrap();
+        assert!(manager.register().await.is_err());
+        assert_eq!(manager.metrics().register_failures(), 1);
+        assert_eq!(manager.metrics().registered_instances(), 0);
+    }
+
+    fn cached_manager(ttl: Duration) -> (ServiceManager, MockRegistry) {
+        let config = test_config().cache_ttl(ttl).build().unwrap();
+        let mock = MockRegistry::new();
+        let manager = ServiceManager::with_registry(config, mock.clone()).unwrap();
+        (manager, mock)
+    }
+
+    #[tokio::test]
+    async fn cached_lookups_refresh_in_background() {
+        let (manager, mock) = cached_manager(Duration::from_millis(30));
+        mock.push_instances("users", DEFAULT_GROUP, vec![instance(1, true, true)]);
+        assert_eq!(manager.get_instances("users", None).await.unwrap().len(), 1);
+
+        let both = vec![instance(1, true, true), instance(2, true, true)];
+        mock.push_instances("users", DEFAULT_GROUP, both);
+        assert_eq!(manager.get_instances("users", None).await.unwrap().len(), 1);
+
+        // Expired: served stale once while the refresh runs.
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert_eq!(manager.get_instances("users", None).await.unwrap().len(), 1);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(manager.get_instances("users", None).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn pushed_changes_invalidate_cache() {
+        let (manager, mock) = cached_manager(Duration::from_secs(3600));
+        manager.subscribe("users", |_| {}).await.unwrap();
+        mock.push_instances("users", DEFAULT_GROUP, vec![instance(1, true, true)]);
+        assert_eq!(manager.get_instances("users", None).await.unwrap().len(), 1);
+
+        mock.push_instances("users", DEFAULT_GROUP, Vec::new());
+        assert!(
+            manager
+                .get_instances("users", None)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn cross_namespace_lookup_needs_nacos() {
+        let (manager, _mock) = mock_manager();
+        let err = manager
+            .get_instances_in_namespace("infra", "users", None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Unsupported(_)));
+    }
+
+    #[tokio::test]
+    async fn watch_polling_fires_on_changes_only() {
+        let (manager, mock) = mock_manager();
+        mock.push_instances("users", DEFAULT_GROUP, vec![instance(1, true, true)]);
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        let watcher = manager.watch_polling("users", Duration::from_millis(10), move |list| {
+            sink.lock().unwrap().push(list.len());
+        });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(*seen.lock().unwrap(), [1]);
+
+        mock.push_instances(
+            "users",
+            DEFAULT_GROUP,
+            vec![instance(1, true, true), instance(2, true, true)],
+        );
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(*seen.lock().unwrap(), [1, 2]);
+
+        drop(manager);
+        tokio::time::timeout(Duration::from_secs(1), watcher)
+            .await
+            .expect("watcher outlived its manager")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn keepalive_exits_when_manager_is_dropped() {
+        let (manager, _mock) = mock_manager();
+        let keepalive = manager.spawn_keepalive(Duration::from_millis(10));
+        drop(manager);
+        tokio::time::timeout(Duration::from_secs(1), keepalive)
+            .await
+            .expect("keepalive outlived its manager")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn ping_reaches_registry() {
+        let manager =
+            ServiceManager::with_registry(test_config().build().unwrap(), MockRegistry::new())
+                .unwrap();
+        manager.ping().await.unwrap();
+
+        // Falls back to an instance query when listing services is unsupported.
+        let manager =
+            ServiceManager::with_registry(test_config().build().unwrap(), WedgedRegistry).unwrap();
+        manager.ping().await.unwrap();
+    }
+
+    /// Registry that has always already forgotten the instance on deregister.
+    struct ForgetfulRegistry;
+
+    #[async_trait::async_trait]
+    impl Registry for ForgetfulRegistry {
+        async fn register(&self, _: &str, _: &str, _: ServiceInstance) -> Result<()> {
+            Ok(())
+        }
+
+        async fn deregister(&self, _: &str, _: &str, _: ServiceInstance) -> Result<()> {
+            Err(Error::Nacos(nacos_sdk::api::error::Error::ErrResponse(
+                None, 404, 20004, None,
+            )))
+        }
+
+        async fn get_instances(&self, _: &str, _: &str) -> Result<Vec<ServiceInstance>> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn deregister_tolerates_instance_not_found() {
+        let config = test_config().build().unwrap();
+        let manager = ServiceManager::with_registry(config, ForgetfulRegistry).unwrap();
+        manager.register().await.unwrap();
+        manager.deregister().await.unwrap();
+        manager.deregister().await.unwrap();
+        assert!(!manager.registered());
+    }
+
+    #[tokio::test]
+    async fn failed_deregister_keeps_registered_flag() {
+        let config = test_config()
+            .timeout(Duration::from_millis(20))
+            .build()
+            .unwrap();
+        let manager = ServiceManager::with_registry(config, WedgedRegistry).unwrap();
+        manager.inner.registered.store(true, Ordering::Release);
+        assert!(manager.deregister().await.is_err());
+        assert!(manager.registered());
+    }
+
+    /// Registry whose calls never complete in a reasonable time.
+    struct WedgedRegistry;
+
+    #[async_trait::async_trait]
+    impl Registry for WedgedRegistry {
+        async fn register(&self, _: &str, _: &str, _: ServiceInstance) -> Result<()> {
+            tokio::time::sleep(Duration::from_secs(3600)).await;
+            Ok(())
+        }
+
+        async fn deregister(&self, _: &str, _: &str, _: ServiceInstance) -> Result<()> {
+            tokio::time::sleep(Duration::from_secs(3600)).await;
+            Ok(())
+        }
+
+        async fn get_instances(&self, _: &str, _: &str) -> Result<Vec<ServiceInstance>> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn register_and_deregister_respect_timeout() {
+        let timeout = Duration::from_millis(20);
+        let config = test_config().timeout(timeout).build().unwrap();
+        let manager = ServiceManager::with_registry(config, WedgedRegistry).unwrap();
+
+        let err = manager.register().await.unwrap_err();
+        assert!(
+            matches!(err, Error::Timeout { operation: "register", timeout: t } if t == timeout)
+        );
+        assert!(!manager.registered());
+
+        let err = manager.deregister().await.unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Timeout {
+                operation: "deregister",
+                ..
+            }
+        ));
+
+        let err = manager
+            .register_batch(vec![("users".to_string(), instance(1, true, true))])
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Timeout {
+                operation: "register_batch",
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+use nacos_sdk::api::naming::ServiceInstance;
+
+/// Registration counters of a [`ServiceManager`](crate::ServiceManager), read via
+/// [`ServiceManager::metrics`](crate::ServiceManager::metrics) and shared by all its clones.
+///
+/// Every successful register call counts, including the re-registrations that push updates
+/// such as [`set_weight`](crate::ServiceManager::set_weight) or that the keepalive watchdog
+/// performs. A call spanning several groups counts once, while
+/// [`register_batch`](crate::ServiceManager::register_batch) counts every instance.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    registrations: AtomicU64,
+    deregistrations: AtomicU64,
+    register_failures: AtomicU64,
+    deregister_failures: AtomicU64,
+    registered: Mutex<HashSet<(String, String, i32)>>,
+}
+
+impl Metrics {
+    /// Number of successful register calls.
+    pub fn registrations(&self) -> u64 {
+        self.registrations.load(Ordering::Relaxed)
+    }
+
+    /// Number of successful deregister calls.
+    pub fn deregistrations(&self) -> u64 {
+        self.deregistrations.load(Ordering::Relaxed)
+    }
+
+    /// Number of register calls that failed, including timeouts.
+    pub fn register_failures(&self) -> u64 {
+        self.register_failures.load(Ordering::Relaxed)
+    }
+
+    /// Number of deregister calls that failed, including timeouts.
+    pub fn deregister_failures(&self) -> u64 {
+        self.deregister_failures.load(Ordering::Relaxed)
+    }
+
+    /// Number of distinct instances (by service, host and port) this manager currently has
+    /// registered.
+    pub fn registered_instances(&self) -> usize {
+        self.registered_set().len()
+    }
+
+    pub(crate) fn record_register(&self, service_name: &str, instance: &ServiceInstance, ok: bool) {
+        if ok {
+            self.registrations.fetch_add(1, Ordering::Relaxed);
+            self.registered_set().insert(key(service_name, instance));
+        } else {
+            self.register_failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn record_deregister(
+        &self,
+        service_name: &str,
+        instance: &ServiceInstance,
+        ok: bool,
+    ) {
+        if ok {
+            self.deregistrations.fetch_add(1, Ordering::Relaxed);
+            self.registered_set().remove(&key(service_name, instance));
+        } else {
+            self.deregister_failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn registered_set(&self) -> std::sync::MutexGuard<'_, HashSet<(String, String, i32)>> {
+        self.registered
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn key(service_name: &str, instance: &ServiceInstance) -> (String, String, i32) {
+    (service_name.to_string(), instance.ip.clone(), instance.port)
+}
@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use async_trait::async_trait;
+use nacos_sdk::api::naming::ServiceInstance;
+
+use crate::error::Result;
+use crate::registry::Registry;
+use crate::subscription::InstanceCallback;
+
+type Key = (String, String);
+
+/// In-memory [`Registry`] that records registrations instead of talking to a server.
+///
+/// Clones share state, so a test can keep one handle for assertions while the
+/// [`ServiceManager`](crate::ServiceManager) owns another. Subscriptions are supported:
+/// callbacks fire synchronously on every change, including [`push_instances`](Self::push_instances).
+///
+/// ```
+/// use ez_rust_discovery::{MockRegistry, ServiceConfig, ServiceManager, DEFAULT_GROUP};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> ez_rust_discovery::Result<()> {
+/// let config = ServiceConfig::builder()
+///     .nacos_addr("127.0.0.1:8848")
+///     .namespace("public")
+///     .service_name("orders")
+///     .service_host("10.0.0.9")
+///     .service_port(8080)
+///     .build()?;
+/// let mock = MockRegistry::new();
+/// let manager = ServiceManager::with_registry(config, mock.clone())?;
+///
+/// manager.register().await?;
+/// assert_eq!(mock.registered_instances("orders", DEFAULT_GROUP).len(), 1);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct MockRegistry {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    instances: HashMap<Key, Vec<ServiceInstance>>,
+    subscribers: HashMap<Key, InstanceCallback>,
+    deregistered: HashMap<Key, Vec<ServiceInstance>>,
+}
+
+impl MockRegistry {
+    /// An empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Instances currently registered under `service_name` in `group`.
+    pub fn registered_instances(&self, service_name: &str, group: &str) -> Vec<ServiceInstance> {
+        self.state()
+            .instances
+            .get(&key(service_name, group))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Replace the instance list of `service_name` in `group`, notifying subscribers.
+    pub fn push_instances(&self, service_name: &str, group: &str, instances: Vec<ServiceInstance>) {
+        let key = key(service_name, group);
+        self.state().instances.insert(key.clone(), instances);
+        self.notify(&key);
+    }
+
+    /// Every instance deregistered from `service_name` in `group`, oldest first.
+    pub fn deregistered_instances(&self, service_name: &str, group: &str) -> Vec<ServiceInstance> {
+        self.state()
+            .deregistered
+            .get(&key(service_name, group))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Invoke the subscriber of `key`, if any, outside of the state lock.
+    fn notify(&self, key: &Key) {
+        let (callback, instances) = {
+            let state = self.state();
+            let Some(callback) = state.subscribers.get(key).cloned() else {
+                return;
+            };
+            (
+                callback,
+                state.instances.get(key).cloned().unwrap_or_default(),
+            )
+        };
+        callback(instances);
+    }
+}
+
+#[async_trait]
+impl Registry for MockRegistry {
+    async fn register(
+        &self,
+        service_name: &str,
+        group: &str,
+        instance: ServiceInstance,
+    ) -> Result<()> {
+        let key = key(service_name, group);
+        {
+            let mut state = self.state();
+            let list = state.instances.entry(key.clone()).or_default();
+            list.retain(|i| !(i.ip == instance.ip && i.port == instance.port));
+            list.push(instance);
+        }
+        self.notify(&key);
+        Ok(())
+    }
+
+    async fn deregister(
+        &self,
+        service_name: &str,
+        group: &str,
+        instance: ServiceInstance,
+    ) -> Result<()> {
+        let key = key(service_name, group);
+        {
+            let mut state = self.state();
+            if let Some(list) = state.instances.get_mut(&key) {
+                list.retain(|i| !(i.ip == instance.ip && i.port == instance.port));
+            }
+            state
+                .deregistered
+                .entry(key.clone())
+                .or_default()
+                .push(instance);
+        }
+        self.notify(&key);
+        Ok(())
+    }
+
+    async fn get_instances(&self, service_name: &str, group: &str) -> Result<Vec<ServiceInstance>> {
+        Ok(self.registered_instances(service_name, group))
+    }
+
+    async fn list_services(
+        &self,
+        group: &str,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<String>, u32)> {
+        let mut names: Vec<String> = self
+            .state()
+            .instances
+            .iter()
+            .filter(|((_, g), instances)| g == group && !instances.is_empty())
+            .map(|((name, _), _)| name.clone())
+            .collect();
+        names.sort();
+        let total = u32::try_from(names.len()).unwrap_or(u32::MAX);
+        let skip = page.saturating_sub(1).saturating_mul(page_size) as usize;
+        let page = names
+            .into_iter()
+            .skip(skip)
+            .take(page_size as usize)
+            .collect();
+        Ok((page, total))
+    }
+
+    async fn subscribe(
+        &self,
+        service_name: &str,
+        group: &str,
+        callback: InstanceCallback,
+    ) -> Result<()> {
+        self.state()
+            .subscribers
+            .insert(key(service_name, group), callback);
+        Ok(())
+    }
+
+    async fn unsubscribe(&self, service_name: &str, group: &str) -> Result<()> {
+        self.state().subscribers.remove(&key(service_name, group));
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for MockRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.state();
+        f.debug_struct("MockRegistry")
+            .field("instances", &state.instances)
+            .field("subscribed", &state.subscribers.keys().collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+fn key(service_name: &str, group: &str) -> Key {
+    (service_name.to_string(), group.to_string())
+}
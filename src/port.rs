@@ -0,0 +1,62 @@
+use std::fmt;
+
+use crate::error::{Error, Result};
+
+/// A validated TCP port in `1..=65535`.
+///
+/// Every port is checked exactly once: the service port by
+/// [`ServiceConfig::validate`](crate::ServiceConfig::validate) (which `build` runs), the HTTP
+/// port when `build` formats it into metadata, and metadata ports when
+/// [`InstanceExt`](crate::InstanceExt) reads them back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Port(u16);
+
+impl Port {
+    /// Accept `port` unless it is zero; `field` names the setting in the error.
+    pub(crate) fn new(port: u16, field: &str) -> Result<Self> {
+        if port == 0 {
+            return Err(Error::invalid_config(format!(
+                "invalid `{field}` = `0`: must be in 1..=65535"
+            )));
+        }
+        Ok(Self(port))
+    }
+
+    /// Parse a decimal port, ignoring surrounding whitespace; `field` names the setting in
+    /// the error.
+    pub(crate) fn parse(value: &str, field: &str) -> Result<Self> {
+        let port = value.trim().parse().map_err(|_| {
+            Error::invalid_config(format!("invalid `{field}` = `{value}`: bad port"))
+        })?;
+        Self::new(port, field)
+    }
+
+    pub(crate) fn get(self) -> u16 {
+        self.0
+    }
+}
+
+impl fmt::Display for Port {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_validates_range() {
+        assert_eq!(Port::parse(" 9000 ", "port").unwrap().get(), 9000);
+        assert_eq!(Port::parse("65535", "port").unwrap().to_string(), "65535");
+        for bad in ["0", "65536", "-1", "grpc", ""] {
+            let err = Port::parse(bad, "port").unwrap_err();
+            assert!(
+                matches!(err, Error::InvalidConfig(ref m) if m.contains("`port`")),
+                "{bad}"
+            );
+        }
+    }
+}
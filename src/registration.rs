@@ -0,0 +1,32 @@
+/// Details of a completed registration, returned by
+/// [`ServiceManager::register_detailed`](crate::ServiceManager::register_detailed).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Registration {
+    /// Instance id as reported by the registry, or the configured
+    /// [`instance_id`](crate::ServiceConfigBuilder::instance_id) if the registry did not list
+    /// one; `None` when neither is known.
+    pub instance_id: Option<String>,
+    /// Service the instance was registered under.
+    pub service_name: String,
+    /// Group the instance was registered in.
+    pub group: String,
+    /// Advertised host.
+    pub host: String,
+    /// Advertised port.
+    pub port: u16,
+}
+
+impl std::fmt::Display for Registration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}@@{} {}:{}",
+            self.group, self.service_name, self.host, self.port
+        )?;
+        if let Some(instance_id) = &self.instance_id {
+            write!(f, " id={instance_id}")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use async_trait::async_trait;
+use nacos_sdk::api::naming::{
+    NamingEventListener, NamingService, NamingServiceBuilder, ServiceInstance,
+};
+use nacos_sdk::api::props::ClientProps;
+
+use crate::config::ServiceConfig;
+use crate::error::{Error, Result};
+use crate::subscription::{CallbackListener, InstanceCallback};
+
+/// A service registry backend that [`ServiceManager`](crate::ServiceManager) talks to.
+///
+/// [`NacosRegistry`] is the default implementation; others (e.g. `ConsulRegistry` behind the
+/// `consul` feature) can be plugged in via
+/// [`ServiceManager::with_registry`](crate::ServiceManager::with_registry).
+#[async_trait]
+pub trait Registry: Send + Sync + 'static {
+    /// Register `instance` under `service_name` in `group`.
+    async fn register(
+        &self,
+        service_name: &str,
+        group: &str,
+        instance: ServiceInstance,
+    ) -> Result<()>;
+
+    /// Register several `instances` under `service_name` in `group`.
+    ///
+    /// The default registers them one by one; backends with a batch API override it to save
+    /// round-trips.
+    async fn register_batch(
+        &self,
+        service_name: &str,
+        group: &str,
+        instances: Vec<ServiceInstance>,
+    ) -> Result<()> {
+        for instance in instances {
+            self.register(service_name, group, instance).await?;
+        }
+        Ok(())
+    }
+
+    /// Deregister `instance` from `service_name` in `group`.
+    async fn deregister(
+        &self,
+        service_name: &str,
+        group: &str,
+        instance: ServiceInstance,
+    ) -> Result<()>;
+
+    /// List every known instance of `service_name` in `group`.
+    async fn get_instances(&self, service_name: &str, group: &str) -> Result<Vec<ServiceInstance>>;
+
+    /// List the names of the services registered in `group`, one page at a time.
+    ///
+    /// `page` is 1-based. Returns the names on the requested page together with the total
+    /// number of services in the group. Backends that cannot enumerate services return
+    /// [`Error::Unsupported`] (the default).
+    async fn list_services(
+        &self,
+        group: &str,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<String>, u32)> {
+        let _ = (group, page, page_size);
+        Err(Error::Unsupported(
+            "this registry does not support listing services".to_string(),
+        ))
+    }
+
+    /// Invoke `callback` with the current instance list whenever `service_name` in `group`
+    /// changes. Subscribing again replaces the previous callback.
+    ///
+    /// Backends without push support return [`Error::Unsupported`] (the default).
+    async fn subscribe(
+        &self,
+        service_name: &str,
+        group: &str,
+        callback: InstanceCallback,
+    ) -> Result<()> {
+        let _ = (service_name, group, callback);
+        Err(Error::Unsupported(
+            "this registry does not support subscriptions".to_string(),
+        ))
+    }
+
+    /// Remove the callback installed by [`subscribe`](Self::subscribe); a no-op when there
+    /// is none.
+    async fn unsubscribe(&self, service_name: &str, group: &str) -> Result<()> {
+        let _ = (service_name, group);
+        Err(Error::Unsupported(
+            "this registry does not support subscriptions".to_string(),
+        ))
+    }
+}
+
+type Listeners = HashMap<(String, String), Arc<dyn NamingEventListener>>;
+
+/// [`Registry`] backed by a nacos-sdk `NamingService`.
+#[derive(Clone)]
+pub struct NacosRegistry {
+    naming: NamingService,
+    listeners: Arc<Mutex<Listeners>>,
+}
+
+impl NacosRegistry {
+    /// Wrap an already configured `NamingService`.
+    pub fn new(naming: NamingService) -> Self {
+        Self {
+            naming,
+            listeners: Arc::default(),
+        }
+    }
+
+    /// Build a Nacos client from the connection settings in `config`.
+    pub async fn connect(config: &ServiceConfig) -> Result<Self> {
+        let mut client_props = ClientProps::new()
+            .server_addr(config.nacos_server_list())
+            .namespace(&config.namespace);
+        if let Some(endpoint) = &config.endpoint {
+            client_props = client_props.endpoint(endpoint);
+        }
+        if let Some(app_name) = config.client_app_name() {
+            client_props = client_props.app_name(app_name);
+        }
+
+        let auth_enabled = config.auth.is_some();
+        if let Some((user, pass)) = config.auth.as_ref() {
+            client_props = client_props.auth_username(user).auth_password(pass);
+        }
+        #[cfg(feature = "aliyun-auth")]
+        if let Some((access_key, secret_key)) = config.access_key.as_ref() {
+            client_props = client_props
+                .auth_access_key(access_key)
+                .auth_access_secret(secret_key);
+        }
+
+        let mut builder = NamingServiceBuilder::new(client_props);
+        if auth_enabled {
+            // Enable the HTTP auth plugin; otherwise username/password would be ignored.
+            builder = builder.enable_auth_plugin_http();
+        }
+        #[cfg(feature = "aliyun-auth")]
+        if config.access_key.is_some() {
+            builder = builder.enable_auth_plugin_aliyun();
+        }
+        Ok(Self::new(builder.build().await?))
+    }
+
+    /// The wrapped `NamingService`.
+    pub fn naming_service(&self) -> &NamingService {
+        &self.naming
+    }
+}
+
+#[async_trait]
+impl Registry for NacosRegistry {
+    async fn register(
+        &self,
+        service_name: &str,
+        group: &str,
+        instance: ServiceInstance,
+    ) -> Result<()> {
+        self.naming
+            .register_instance(service_name.to_string(), Some(group.to_string()), instance)
+            .await?;
+        Ok(())
+    }
+
+    async fn register_batch(
+        &self,
+        service_name: &str,
+        group: &str,
+        instances: Vec<ServiceInstance>,
+    ) -> Result<()> {
+        // Nacos only batches ephemeral instances; register persistent ones individually.
+        if !instances.iter().all(|i| i.ephemeral) {
+            for instance in instances {
+                self.register(service_name, group, instance).await?;
+            }
+            return Ok(());
+        }
+        self.naming
+            .batch_register_instance(service_name.to_string(), Some(group.to_string()), instances)
+            .await?;
+        Ok(())
+    }
+
+    async fn deregister(
+        &self,
+        service_name: &str,
+        group: &str,
+        instance: ServiceInstance,
+    ) -> Result<()> {
+        self.naming
+            .deregister_instance(service_name.to_string(), Some(group.to_string()), instance)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_instances(&self, service_name: &str, group: &str) -> Result<Vec<ServiceInstance>> {
+        let instances = self
+            .naming
+            .get_all_instances(
+                service_name.to_string(),
+                Some(group.to_string()),
+                Vec::new(),
+                false,
+            )
+            .await?;
+        Ok(instances)
+    }
+
+    async fn list_services(
+        &self,
+        group: &str,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<String>, u32)> {
+        let to_i32 = |value: u32, field: &str| {
+            i32::try_from(value).map_err(|_| {
+                Error::invalid_config(format!("invalid `{field}` = `{value}`: too large"))
+            })
+        };
+        let (names, total) = self
+            .naming
+            .get_service_list(
+                to_i32(page, "page")?,
+                to_i32(page_size, "page_size")?,
+                Some(group.to_string()),
+            )
+            .await?;
+        Ok((names, u32::try_from(total).unwrap_or_default()))
+    }
+
+    async fn subscribe(
+        &self,
+        service_name: &str,
+        group: &str,
+        callback: InstanceCallback,
+    ) -> Result<()> {
+        self.unsubscribe(service_name, group).await?;
+        let listener: Arc<dyn NamingEventListener> = Arc::new(CallbackListener::new(callback));
+        self.naming
+            .subscribe(
+                service_name.to_string(),
+                Some(group.to_string()),
+                Vec::new(),
+                Arc::clone(&listener),
+            )
+            .await?;
+        self.listeners
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert((service_name.to_string(), group.to_string()), listener);
+        Ok(())
+    }
+
+    async fn unsubscribe(&self, service_name: &str, group: &str) -> Result<()> {
+        let listener = self
+            .listeners
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&(service_name.to_string(), group.to_string()));
+        let Some(listener) = listener else {
+            return Ok(());
+        };
+        self.naming
+            .unsubscribe(
+                service_name.to_string(),
+                Some(group.to_string()),
+                Vec::new(),
+                listener,
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for NacosRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NacosRegistry")
+            .field("naming", &self.naming)
+            .finish_non_exhaustive()
+    }
+}
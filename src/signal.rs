@@ -0,0 +1,78 @@
+use std::future::Future;
+
+use crate::error::Result;
+
+/// Wait until the process receives a termination signal.
+///
+/// On Unix this resolves on either `SIGINT` or `SIGTERM`; elsewhere it waits for Ctrl+C.
+pub async fn shutdown_signal() -> Result<()> {
+    listen()?.await.map(|_| ())
+}
+
+/// Block the current thread until the process receives a termination signal, like
+/// [`shutdown_signal`] (so `SIGTERM` counts too, not only Ctrl+C).
+///
+/// For synchronous programs, between [`register_blocking`](crate::ServiceManager::register_blocking)
+/// and [`deregister_blocking`](crate::ServiceManager::deregister_blocking). From within a tokio
+/// runtime this returns an error; `.await` [`shutdown_signal`] there.
+pub fn block_until_shutdown_signal() -> Result<()> {
+    crate::manager::block_on(shutdown_signal())
+}
+
+/// The termination signal [`listen`] observed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Received {
+    /// `SIGINT`, or Ctrl+C on non-Unix.
+    Interrupt,
+    /// `SIGTERM`.
+    Terminate,
+}
+
+impl Received {
+    /// Conventional exit status of a process killed by this signal (`128 + signo`).
+    pub(crate) fn exit_code(self) -> i32 {
+        match self {
+            Self::Interrupt => 130,
+            Self::Terminate => 143,
+        }
+    }
+}
+
+/// Install the handlers behind [`shutdown_signal`] right away and return the future that
+/// resolves on the first signal, so no signal is missed between installing and awaiting.
+///
+/// Must be called from within a tokio runtime.
+pub(crate) fn listen() -> Result<impl Future<Output = Result<Received>>> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        let mut interrupt = signal(SignalKind::interrupt())?;
+        let mut terminate = signal(SignalKind::terminate())?;
+        Ok(async move {
+            Ok(tokio::select! {
+                _ = interrupt.recv() => Received::Interrupt,
+                _ = terminate.recv() => Received::Terminate,
+            })
+        })
+    }
+    #[cfg(not(unix))]
+    {
+        Ok(async {
+            tokio::signal::ctrl_c().await?;
+            Ok(Received::Interrupt)
+        })
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_codes_follow_the_signal_number() {
+        assert_eq!(Received::Interrupt.exit_code(), 130);
+        assert_eq!(Received::Terminate.exit_code(), 143);
+    }
+}
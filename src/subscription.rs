@@ -0,0 +1,23 @@
+use std::sync::Arc;
+
+use nacos_sdk::api::naming::{NamingChangeEvent, NamingEventListener, ServiceInstance};
+
+/// Callback invoked with the full, current instance list whenever a subscribed service changes.
+pub type InstanceCallback = Arc<dyn Fn(Vec<ServiceInstance>) + Send + Sync>;
+
+/// Adapts an [`InstanceCallback`] to nacos-sdk's [`NamingEventListener`].
+pub(crate) struct CallbackListener {
+    callback: InstanceCallback,
+}
+
+impl CallbackListener {
+    pub(crate) fn new(callback: InstanceCallback) -> Self {
+        Self { callback }
+    }
+}
+
+impl NamingEventListener for CallbackListener {
+    fn event(&self, event: Arc<NamingChangeEvent>) {
+        (self.callback)(event.instances.clone().unwrap_or_default());
+    }
+}
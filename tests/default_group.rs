@@ -0,0 +1,66 @@
+// The group override is process-wide state, so it is exercised in its own test binary where
+// it cannot leak into the unit tests that expect `DEFAULT_GROUP`.
+
+#![allow(clippy::unwrap_used, clippy::expect_used, missing_docs)]
+
+use std::sync::Mutex;
+
+use ez_rust_discovery::{
+    DEFAULT_GROUP, ServiceConfig, clear_default_group, default_group, set_default_group,
+};
+
+/// Serializes the tests in this binary, which all touch the override.
+static OVERRIDE: Mutex<()> = Mutex::new(());
+
+fn builder() -> ez_rust_discovery::ServiceConfigBuilder {
+    ServiceConfig::builder()
+        .nacos_addr("127.0.0.1:8848")
+        .namespace("public")
+        .service_name("svc")
+        .service_host("10.0.0.1")
+        .service_port(9000)
+}
+
+#[test]
+fn set_default_group_applies_to_builders_without_group() {
+    let _serial = OVERRIDE.lock().unwrap_or_else(|e| e.into_inner());
+    assert_eq!(default_group(), DEFAULT_GROUP);
+
+    set_default_group("ORG_GROUP");
+    assert_eq!(builder().build().unwrap().group, "ORG_GROUP");
+    assert_eq!(builder().group("other").build().unwrap().group, "other");
+
+    clear_default_group();
+    assert_eq!(builder().build().unwrap().group, DEFAULT_GROUP);
+}
+
+#[cfg(feature = "test-util")]
+#[test]
+fn deregister_targets_the_group_resolved_at_build() {
+    use ez_rust_discovery::{MockRegistry, ServiceManager};
+
+    let _serial = OVERRIDE.lock().unwrap_or_else(|e| e.into_inner());
+    let mock = MockRegistry::new();
+    let manager = ServiceManager::with_registry(builder().build().unwrap(), mock.clone()).unwrap();
+
+    // A later change of the default must not split register and deregister across groups.
+    set_default_group("ORG_GROUP");
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    rt.block_on(manager.register()).unwrap();
+    let registered = mock.registered_instances("svc", DEFAULT_GROUP);
+    rt.block_on(manager.deregister()).unwrap();
+    clear_default_group();
+
+    assert_eq!(manager.group(), DEFAULT_GROUP);
+    assert_eq!(registered.len(), 1);
+    let deregistered = mock.deregistered_instances("svc", DEFAULT_GROUP);
+    assert_eq!(deregistered.len(), 1);
+    assert_eq!(
+        (&deregistered[0].ip, deregistered[0].port),
+        (&registered[0].ip, registered[0].port)
+    );
+    assert!(mock.registered_instances("svc", DEFAULT_GROUP).is_empty());
+    assert!(mock.deregistered_instances("svc", "ORG_GROUP").is_empty());
+}
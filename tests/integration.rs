@@ -1,6 +1,7 @@
 // Integration tests against a real Nacos instance; skipped by default via `#[ignore]`.
 //
-// Run explicitly: `cargo test -- --ignored`.
+// Run explicitly: `cargo test -- --ignored`. The server address is taken from `NACOS_ADDR`
+// and defaults to `127.0.0.1:8848`.
 
 #![allow(clippy::unwrap_used, clippy::expect_used, missing_docs)]
 
@@ -12,11 +13,13 @@ use ez_rust_discovery::{ServiceConfig, ServiceManager};
 #[tokio::test]
 #[ignore = "requires a running Nacos server"]
 async fn register_and_deregister_against_local_nacos() {
+    let nacos_addr = std::env::var("NACOS_ADDR").unwrap_or_else(|_| "127.0.0.1:8848".into());
     let config = ServiceConfig::builder()
-        .nacos_addr("192.168.14.121:8848")
+        .nacos_addr(nacos_addr)
         .namespace("public")
         .service_name("ez-rust-discovery-it")
         .service_port(19999)
+        .cluster_name("it-cluster")
         .build()
         .expect("config build failed");
 
@@ -25,5 +28,16 @@ async fn register_and_deregister_against_local_nacos() {
         .expect("manager init failed");
     manager.register().await.expect("register failed");
     tokio::time::sleep(Duration::from_secs(10)).await;
+
+    let instances = manager
+        .get_instances(manager.service_name(), None)
+        .await
+        .expect("query failed");
+    let own = instances
+        .iter()
+        .find(|i| i.port == 19999)
+        .expect("registered instance not found");
+    assert_eq!(own.cluster_name.as_deref(), Some("it-cluster"));
+
     manager.deregister().await.expect("deregister failed");
 }